@@ -2,6 +2,7 @@
 //!
 //! Handles starting and stopping the AI server sidecar process.
 
+use crate::api_auth::{issue_api_token, AI_SERVER_CALLER, API_TOKEN_ENV};
 use crate::ports::{
     get_port_info,
     is_port_listening,
@@ -282,9 +283,12 @@ pub fn start_ai_server(
             let sidecar = shell
                 .sidecar("moldable-ai-server")
                 .map_err(|e| e.to_string())?;
+            // A fresh API token per spawn, so a restart invalidates the old one
+            let api_token = issue_api_token(AI_SERVER_CALLER)?;
             let sidecar = sidecar
                 .env("MOLDABLE_AI_PORT", actual_port.to_string())
-                .env("MOLDABLE_AI_SERVER", "1");
+                .env("MOLDABLE_AI_SERVER", "1")
+                .env(API_TOKEN_ENV, api_token);
             sidecar
                 .spawn()
                 .map_err(|e| format!("Failed to spawn AI server: {}", e))
@@ -0,0 +1,258 @@
+//! Authentication for the local HTTP API
+//!
+//! The API server only listens on 127.0.0.1, but any local process, or a web
+//! page that can reach the port, could still call it. Every request must
+//! therefore carry a bearer token issued to a known caller. The AI server
+//! sidecar gets its token through the `MOLDABLE_API_TOKEN` environment variable
+//! and a fresh one each time it is (re)started.
+//!
+//! Before the token is checked, requests whose `Host` isn't a loopback name
+//! (DNS rebinding) or that come from a browser origin other than the app's own
+//! webview are rejected.
+
+use crate::api_error::{ApiError, ApiErrorCode};
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Environment variable callers read their token from
+pub const API_TOKEN_ENV: &str = "MOLDABLE_API_TOKEN";
+
+/// Caller name of the AI server sidecar
+pub const AI_SERVER_CALLER: &str = "ai-server";
+
+/// Token length in random bytes (hex encoded, so twice as many characters)
+const API_TOKEN_BYTES: usize = 32;
+
+/// Hosts a request may be addressed to; anything else is a rebound DNS name
+const ALLOWED_HOSTS: &[&str] = &["127.0.0.1", "localhost", "[::1]"];
+
+/// Browser origins allowed to call the API: the app's own webview
+const ALLOWED_ORIGINS: &[&str] = &[
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// Caller name -> current token
+static API_TOKENS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn api_tokens() -> MutexGuard<'static, HashMap<String, String>> {
+    API_TOKENS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The caller a request was authenticated as, available to handlers as a
+/// request extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiCaller(pub String);
+
+/// Generate a new token for `caller`, replacing any token it had before
+pub fn issue_api_token(caller: &str) -> Result<String, String> {
+    let mut bytes = [0u8; API_TOKEN_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate API token: {}", e))?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    api_tokens().insert(caller.to_string(), token.clone());
+    Ok(token)
+}
+
+/// Stop accepting `caller`'s token
+pub fn revoke_api_token(caller: &str) {
+    api_tokens().remove(caller);
+}
+
+/// Compare without exiting early, so response timing doesn't leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The caller holding `token`, if any
+fn caller_for_token(token: &str) -> Option<String> {
+    api_tokens()
+        .iter()
+        .find(|(_, issued)| constant_time_eq(issued.as_bytes(), token.as_bytes()))
+        .map(|(caller, _)| caller.clone())
+}
+
+/// Host name of a `Host` header value, without the port
+fn host_name(host: &str) -> &str {
+    if host.starts_with('[') {
+        // IPv6 literal: [::1]:39102
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    host.split(':').next().unwrap_or(host)
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Check a request's `Host`, `Origin` and bearer token, returning the
+/// authenticated caller or the error to reject it with
+fn authorize(headers: &HeaderMap) -> Result<String, ApiError> {
+    let host = header_str(headers, header::HOST).map(host_name);
+    if !host.is_some_and(|host| ALLOWED_HOSTS.contains(&host.to_ascii_lowercase().as_str())) {
+        return Err(ApiError::new(ApiErrorCode::Forbidden, "Invalid Host header"));
+    }
+
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let allowed = origin
+            .to_str()
+            .is_ok_and(|origin| ALLOWED_ORIGINS.contains(&origin));
+        if !allowed {
+            return Err(ApiError::new(ApiErrorCode::Forbidden, "Origin not allowed"));
+        }
+    }
+
+    let token = header_str(headers, header::AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| ApiError::new(ApiErrorCode::Unauthorized, "Missing API token"))?;
+
+    caller_for_token(token)
+        .ok_or_else(|| ApiError::new(ApiErrorCode::Unauthorized, "Invalid API token"))
+}
+
+/// Axum middleware rejecting requests that fail `authorize`. Accepted
+/// requests carry an `ApiCaller` extension.
+pub async fn require_api_auth(mut request: Request, next: Next) -> Response {
+    match authorize(request.headers()) {
+        Ok(caller) => {
+            request.extensions_mut().insert(ApiCaller(caller));
+            next.run(request).await
+        }
+        Err(error) => {
+            warn!(
+                "Rejected API request to {}: {}",
+                request.uri().path(),
+                error.message
+            );
+            let unauthorized = error.code == ApiErrorCode::Unauthorized;
+            let mut response = error.into_response();
+            if unauthorized {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(entries: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn bearer(token: &str) -> String {
+        format!("Bearer {}", token)
+    }
+
+    fn unauthorized(message: &str) -> ApiError {
+        ApiError::new(ApiErrorCode::Unauthorized, message)
+    }
+
+    fn forbidden(message: &str) -> ApiError {
+        ApiError::new(ApiErrorCode::Forbidden, message)
+    }
+
+    #[test]
+    fn test_issue_api_token_rotates_per_caller() {
+        let first = issue_api_token("test-rotate").unwrap();
+        assert_eq!(first.len(), API_TOKEN_BYTES * 2);
+        assert_eq!(caller_for_token(&first).as_deref(), Some("test-rotate"));
+
+        let second = issue_api_token("test-rotate").unwrap();
+        assert_ne!(first, second);
+        assert!(caller_for_token(&first).is_none());
+        assert_eq!(caller_for_token(&second).as_deref(), Some("test-rotate"));
+
+        revoke_api_token("test-rotate");
+        assert!(caller_for_token(&second).is_none());
+    }
+
+    #[test]
+    fn test_authorize_accepts_valid_token() {
+        let token = issue_api_token("test-accept").unwrap();
+        let auth = bearer(&token);
+
+        for host in ["127.0.0.1:39102", "localhost:39102", "[::1]:39102", "LOCALHOST"] {
+            let request = headers(&[(header::HOST, host), (header::AUTHORIZATION, &auth)]);
+            assert_eq!(authorize(&request), Ok("test-accept".to_string()), "{}", host);
+        }
+
+        let request = headers(&[
+            (header::HOST, "127.0.0.1:39102"),
+            (header::ORIGIN, "tauri://localhost"),
+            (header::AUTHORIZATION, &auth),
+        ]);
+        assert!(authorize(&request).is_ok());
+        revoke_api_token("test-accept");
+    }
+
+    #[test]
+    fn test_authorize_rejects_missing_or_wrong_token() {
+        let token = issue_api_token("test-reject").unwrap();
+        let host = (header::HOST, "127.0.0.1:39102");
+
+        let missing = authorize(&headers(std::slice::from_ref(&host)));
+        assert_eq!(missing, Err(unauthorized("Missing API token")));
+
+        let wrong = bearer(&format!("{}0", token));
+        let result = authorize(&headers(&[host.clone(), (header::AUTHORIZATION, &wrong)]));
+        assert_eq!(result, Err(unauthorized("Invalid API token")));
+
+        let basic = format!("Basic {}", token);
+        let result = authorize(&headers(&[host, (header::AUTHORIZATION, &basic)]));
+        assert_eq!(result, Err(unauthorized("Missing API token")));
+        revoke_api_token("test-reject");
+    }
+
+    #[test]
+    fn test_authorize_blocks_rebinding_and_foreign_origins() {
+        let token = issue_api_token("test-origin").unwrap();
+        let auth = bearer(&token);
+
+        for host in ["evil.example:39102", "127.0.0.1.evil.example", "localhostx"] {
+            let request = headers(&[(header::HOST, host), (header::AUTHORIZATION, &auth)]);
+            assert_eq!(authorize(&request), Err(forbidden("Invalid Host header")));
+        }
+        let no_host = headers(&[(header::AUTHORIZATION, &auth)]);
+        assert_eq!(authorize(&no_host), Err(forbidden("Invalid Host header")));
+
+        for origin in ["https://evil.example", "null", "http://localhost:3000"] {
+            let request = headers(&[
+                (header::HOST, "127.0.0.1:39102"),
+                (header::ORIGIN, origin),
+                (header::AUTHORIZATION, &auth),
+            ]);
+            assert_eq!(authorize(&request), Err(forbidden("Origin not allowed")));
+        }
+        revoke_api_token("test-origin");
+    }
+
+    #[test]
+    fn test_host_name_strips_port() {
+        assert_eq!(host_name("127.0.0.1:39102"), "127.0.0.1");
+        assert_eq!(host_name("localhost"), "localhost");
+        assert_eq!(host_name("[::1]:39102"), "[::1]");
+    }
+}
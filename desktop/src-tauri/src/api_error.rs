@@ -0,0 +1,199 @@
+//! Error model for the local HTTP API
+//!
+//! Handlers return `Result<Json<T>, ApiError>`. An error is sent as
+//! `{ "success": false, "code": "...", "error": "..." }` with the HTTP status
+//! that belongs to its code, so clients can branch on `code` instead of
+//! matching on messages.
+
+use axum::{
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Machine-readable reason an API request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    /// The request body or a parameter is malformed or invalid
+    InvalidRequest,
+    /// The bearer token is missing or unknown
+    Unauthorized,
+    /// The request's `Host` or `Origin` isn't allowed
+    Forbidden,
+    /// The app, checkpoint or other resource doesn't exist
+    NotFound,
+    /// The resource already exists
+    Conflict,
+    /// The desktop app isn't ready to handle the request
+    Unavailable,
+    /// Anything else that went wrong on the server
+    Internal,
+}
+
+impl ApiErrorCode {
+    pub const ALL: [ApiErrorCode; 7] = [
+        ApiErrorCode::InvalidRequest,
+        ApiErrorCode::Unauthorized,
+        ApiErrorCode::Forbidden,
+        ApiErrorCode::NotFound,
+        ApiErrorCode::Conflict,
+        ApiErrorCode::Unavailable,
+        ApiErrorCode::Internal,
+    ];
+
+    /// HTTP status an error with this code is sent with
+    pub fn status(self) -> StatusCode {
+        match self {
+            ApiErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ApiErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ApiErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ApiErrorCode::Conflict => StatusCode::CONFLICT,
+            ApiErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// A failed API request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::InvalidRequest, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::Conflict, message)
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::Unavailable, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::Internal, message)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.code.status()
+    }
+}
+
+/// JSON body of an error response
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiErrorBody {
+    /// Always `false`
+    pub success: bool,
+    pub code: ApiErrorCode,
+    /// Human-readable description of the error
+    pub error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiErrorBody {
+            success: false,
+            code: self.code,
+            error: self.message,
+        };
+        (self.code.status(), Json(body)).into_response()
+    }
+}
+
+/// `Json` extractor that rejects malformed bodies with an `ApiError`
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(request, state)
+            .await
+            .map(|Json(value)| ApiJson(value))
+            .map_err(|rejection| ApiError::invalid_request(rejection.body_text()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+
+    async fn response_json(response: Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_error_codes_have_distinct_statuses() {
+        let mut statuses: Vec<u16> = ApiErrorCode::ALL
+            .iter()
+            .map(|code| code.status().as_u16())
+            .collect();
+        statuses.sort_unstable();
+        statuses.dedup();
+        assert_eq!(statuses.len(), ApiErrorCode::ALL.len());
+    }
+
+    #[tokio::test]
+    async fn test_error_response_body() {
+        let response = ApiError::not_found("App 'ghost' not found").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response_json(response).await,
+            serde_json::json!({
+                "success": false,
+                "code": "not_found",
+                "error": "App 'ghost' not found"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_json_rejects_malformed_body() {
+        #[derive(Debug, serde::Deserialize)]
+        struct AppIdRequest {
+            #[allow(dead_code)]
+            app_id: String,
+        }
+
+        let request = Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"wrong": 1}"#))
+            .unwrap();
+        let error = ApiJson::<AppIdRequest>::from_request(request, &()).await.err().unwrap();
+        assert_eq!(error.code, ApiErrorCode::InvalidRequest);
+        assert!(error.message.contains("app_id"), "{}", error.message);
+
+        let request = Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"app_id": "my-app"}"#))
+            .unwrap();
+        assert!(ApiJson::<AppIdRequest>::from_request(request, &()).await.is_ok());
+    }
+}
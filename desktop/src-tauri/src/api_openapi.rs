@@ -0,0 +1,389 @@
+//! OpenAPI description of the local HTTP API
+//!
+//! Served at `/api/openapi.json` so the ai-server client can be generated
+//! instead of hand-written. Request and response schemas are derived from the
+//! handler types with `schemars`; the operations themselves are listed in
+//! `OPERATIONS`, which has to be kept in sync with the router in `api_server`.
+
+use crate::api_error::ApiErrorCode::{Conflict, InvalidRequest, NotFound, Unavailable};
+use crate::api_error::{ApiErrorBody, ApiErrorCode};
+use crate::api_server::{
+    AppInfoRequest, AppInfoResponse, AppLogsResponse, AppProcessResponse, CheckpointList,
+    CheckpointResponse, CleanupCheckpointsRequest, CreateAppRequest, CreateAppResponse,
+    CreateCheckpointRequest, DeleteAppDataRequest, DeleteAppDataResponse, DeleteAppRequest,
+    DeleteAppResponse, DiffCheckpointsRequest, HealthResponse, ListCheckpointsRequest,
+    RestoreCheckpointRequest, UnregisterAppRequest, UnregisterAppResponse,
+};
+use crate::checkpoints::{CheckpointDiff, CheckpointResult, CleanupResult, RestoreResult};
+use schemars::{generate::SchemaSettings, JsonSchema, Schema, SchemaGenerator};
+use serde_json::{json, Map, Value};
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// A single route of the API server
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    operation_id: &'static str,
+    summary: &'static str,
+    /// JSON body schema, for routes that take one
+    request: Option<SchemaFn>,
+    response: SchemaFn,
+    /// Error codes the handler itself can return, on top of the ones every
+    /// route can (see `COMMON_ERRORS`)
+    errors: &'static [ApiErrorCode],
+}
+
+/// Errors any route can return: the auth middleware's and unexpected failures
+const COMMON_ERRORS: &[ApiErrorCode] = &[
+    ApiErrorCode::Unauthorized,
+    ApiErrorCode::Forbidden,
+    ApiErrorCode::Internal,
+];
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+const OPERATIONS: &[Operation] = &[
+    Operation {
+        method: "get",
+        path: "/health",
+        operation_id: "health",
+        summary: "Check that the API server is up",
+        request: None,
+        response: schema::<HealthResponse>,
+        errors: &[],
+    },
+    Operation {
+        method: "get",
+        path: "/api/openapi.json",
+        operation_id: "getOpenApiDocument",
+        summary: "Get this OpenAPI document",
+        request: None,
+        response: schema::<Value>,
+        errors: &[],
+    },
+    Operation {
+        method: "post",
+        path: "/api/create-app",
+        operation_id: "createApp",
+        summary: "Create an app from the app template and register it",
+        request: Some(schema::<CreateAppRequest>),
+        response: schema::<CreateAppResponse>,
+        errors: &[InvalidRequest, Conflict, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/app-info",
+        operation_id: "getAppInfo",
+        summary: "Get an app's name, path and the workspaces it's installed in",
+        request: Some(schema::<AppInfoRequest>),
+        response: schema::<AppInfoResponse>,
+        errors: &[InvalidRequest, NotFound],
+    },
+    Operation {
+        method: "post",
+        path: "/api/unregister-app",
+        operation_id: "unregisterApp",
+        summary: "Remove an app from the current workspace, keeping its code and data",
+        request: Some(schema::<UnregisterAppRequest>),
+        response: schema::<UnregisterAppResponse>,
+        errors: &[InvalidRequest, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/delete-app-data",
+        operation_id: "deleteAppData",
+        summary: "Delete an app's data in the current workspace",
+        request: Some(schema::<DeleteAppDataRequest>),
+        response: schema::<DeleteAppDataResponse>,
+        errors: &[InvalidRequest, NotFound],
+    },
+    Operation {
+        method: "post",
+        path: "/api/delete-app",
+        operation_id: "deleteApp",
+        summary: "Delete an app's code and data from all workspaces",
+        request: Some(schema::<DeleteAppRequest>),
+        response: schema::<DeleteAppResponse>,
+        errors: &[InvalidRequest, NotFound, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/apps/{id}/start",
+        operation_id: "startApp",
+        summary: "Start an app with its registered command",
+        request: None,
+        response: schema::<AppProcessResponse>,
+        errors: &[NotFound, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/apps/{id}/stop",
+        operation_id: "stopApp",
+        summary: "Stop an app and its process tree",
+        request: None,
+        response: schema::<AppProcessResponse>,
+        errors: &[NotFound, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/apps/{id}/restart",
+        operation_id: "restartApp",
+        summary: "Stop an app if it's running and start it again",
+        request: None,
+        response: schema::<AppProcessResponse>,
+        errors: &[NotFound, Unavailable],
+    },
+    Operation {
+        method: "get",
+        path: "/api/apps/{id}/status",
+        operation_id: "getAppStatus",
+        summary: "Get whether an app is running, with its recent output",
+        request: None,
+        response: schema::<AppProcessResponse>,
+        errors: &[NotFound, Unavailable],
+    },
+    Operation {
+        method: "get",
+        path: "/api/apps/{id}/logs",
+        operation_id: "getAppLogs",
+        summary: "Get an app's install progress and captured output",
+        request: None,
+        response: schema::<AppLogsResponse>,
+        errors: &[NotFound, Unavailable],
+    },
+    Operation {
+        method: "post",
+        path: "/api/checkpoints/create",
+        operation_id: "createCheckpoint",
+        summary: "Capture a checkpoint of a registered app's files",
+        request: Some(schema::<CreateCheckpointRequest>),
+        response: schema::<CheckpointResponse<CheckpointResult>>,
+        errors: &[InvalidRequest, NotFound],
+    },
+    Operation {
+        method: "post",
+        path: "/api/checkpoints/list",
+        operation_id: "listCheckpoints",
+        summary: "List the checkpoints of a conversation",
+        request: Some(schema::<ListCheckpointsRequest>),
+        response: schema::<CheckpointResponse<CheckpointList>>,
+        errors: &[InvalidRequest],
+    },
+    Operation {
+        method: "post",
+        path: "/api/checkpoints/diff",
+        operation_id: "diffCheckpoints",
+        summary: "Diff a checkpoint against another one or the working tree",
+        request: Some(schema::<DiffCheckpointsRequest>),
+        response: schema::<CheckpointResponse<CheckpointDiff>>,
+        errors: &[InvalidRequest],
+    },
+    Operation {
+        method: "post",
+        path: "/api/checkpoints/restore",
+        operation_id: "restoreCheckpoint",
+        summary: "Restore all files, or only some paths, to a checkpoint",
+        request: Some(schema::<RestoreCheckpointRequest>),
+        response: schema::<CheckpointResponse<RestoreResult>>,
+        errors: &[InvalidRequest],
+    },
+    Operation {
+        method: "post",
+        path: "/api/checkpoints/cleanup",
+        operation_id: "cleanupCheckpoints",
+        summary: "Delete all but the most recent checkpoints of a conversation",
+        request: Some(schema::<CleanupCheckpointsRequest>),
+        response: schema::<CheckpointResponse<CleanupResult>>,
+        errors: &[InvalidRequest],
+    },
+];
+
+/// Path parameters of a route, e.g. `id` for `/api/apps/{id}/start`
+fn path_parameters(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .collect()
+}
+
+fn json_content(schema: Schema) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+/// Build the OpenAPI document for the API server
+pub fn openapi_document() -> Value {
+    // Requests are described as they're deserialized and responses as they're
+    // serialized, so optional and defaulted fields come out right on each side
+    let mut requests = SchemaSettings::openapi3().for_deserialize().into_generator();
+    let mut responses = SchemaSettings::openapi3().for_serialize().into_generator();
+    let error_schema = responses.subschema_for::<ApiErrorBody>();
+
+    let mut paths = Map::new();
+    for operation in OPERATIONS {
+        let mut operation_responses = Map::new();
+        operation_responses.insert(
+            "200".to_string(),
+            json!({
+                "description": "Success",
+                "content": json_content((operation.response)(&mut responses)),
+            }),
+        );
+        for code in operation.errors.iter().chain(COMMON_ERRORS) {
+            let status = code.status();
+            let reason = status.canonical_reason().unwrap_or("Error");
+            let code_name = json!(code);
+            let code_name = code_name.as_str().unwrap_or_default();
+            operation_responses.insert(
+                status.as_str().to_string(),
+                json!({
+                    "description": format!("{} (code `{}`)", reason, code_name),
+                    "content": json_content(error_schema.clone()),
+                }),
+            );
+        }
+
+        let mut spec = json!({
+            "operationId": operation.operation_id,
+            "summary": operation.summary,
+            "responses": operation_responses,
+        });
+        let parameters: Vec<Value> = path_parameters(operation.path)
+            .into_iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        if !parameters.is_empty() {
+            spec["parameters"] = Value::Array(parameters);
+        }
+        if let Some(request) = operation.request {
+            spec["requestBody"] = json!({
+                "required": true,
+                "content": json_content(request(&mut requests)),
+            });
+        }
+
+        let item = paths
+            .entry(operation.path.to_string())
+            .or_insert_with(|| json!({}));
+        item[operation.method] = spec;
+    }
+
+    let mut schemas = requests.take_definitions(true);
+    schemas.extend(responses.take_definitions(true));
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Moldable API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Local API of the Moldable desktop app, used by the AI server.",
+        },
+        "servers": [{ "url": format!("http://127.0.0.1:{}", crate::api_server::API_SERVER_PORT) }],
+        "security": [{ "bearerAuth": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": schemas,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every `$ref` in `value`
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference);
+                }
+                map.values().for_each(|value| collect_refs(value, refs));
+            }
+            Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_openapi_document_lists_every_operation() {
+        let document = openapi_document();
+        assert_eq!(document["openapi"], "3.0.3");
+
+        for operation in OPERATIONS {
+            let spec = &document["paths"][operation.path][operation.method];
+            assert_eq!(spec["operationId"], operation.operation_id, "{}", operation.path);
+            assert!(spec["responses"]["200"].is_object());
+            assert!(spec["responses"]["401"].is_object());
+            assert_eq!(spec["requestBody"].is_object(), operation.request.is_some());
+        }
+
+        let start = &document["paths"]["/api/apps/{id}/start"]["post"];
+        assert_eq!(start["parameters"][0]["name"], "id");
+        assert_eq!(start["parameters"][0]["in"], "path");
+        assert_eq!(start["responses"]["404"]["description"], "Not Found (code `not_found`)");
+    }
+
+    #[test]
+    fn test_openapi_document_resolves_all_refs() {
+        let document = openapi_document();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.contains_key("ApiErrorBody"));
+        assert!(schemas.contains_key("CreateAppRequest"));
+
+        let mut refs = Vec::new();
+        collect_refs(&document, &mut refs);
+        assert!(!refs.is_empty());
+        for reference in refs {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected $ref {}", reference));
+            assert!(schemas.contains_key(name), "missing schema {}", name);
+        }
+    }
+
+    #[test]
+    fn test_openapi_schemas_follow_serde() {
+        let document = openapi_document();
+        let schemas = &document["components"]["schemas"];
+
+        // Defaulted request fields aren't required
+        let required = schemas["CreateAppRequest"]["required"].as_array().unwrap();
+        assert!(required.contains(&json!("appId")));
+        assert!(!required.contains(&json!("widgetSize")));
+
+        // Error codes are listed in their wire format, one documented variant each
+        let codes: Vec<&Value> = schemas["ApiErrorCode"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| &variant["enum"][0])
+            .collect();
+        assert_eq!(codes.len(), ApiErrorCode::ALL.len());
+        for code in ApiErrorCode::ALL {
+            assert!(codes.contains(&&serde_json::to_value(code).unwrap()), "{:?}", code);
+        }
+
+        // Checkpoint responses are named after their result
+        let cleanup = &schemas["CleanupResultResponse"];
+        assert!(cleanup["properties"]["success"].is_object());
+        assert!(cleanup["properties"]["snapshotsDeleted"].is_object());
+    }
+
+    #[test]
+    fn test_path_parameters() {
+        assert_eq!(path_parameters("/api/apps/{id}/logs"), vec!["id"]);
+        assert!(path_parameters("/api/create-app").is_empty());
+    }
+}
@@ -2,31 +2,48 @@
 //!
 //! Provides HTTP endpoints that the AI server can call to perform
 //! operations that require access to Rust-side functionality.
-
-use crate::apps::{find_available_port, register_app, unregister_app};
+//!
+//! Every route requires a bearer token (see `api_auth`). Failures are sent as
+//! an `ApiError` (see `api_error`), and the routes are described by the
+//! OpenAPI document at `/api/openapi.json` (see `api_openapi`).
+
+use crate::api_auth::require_api_auth;
+use crate::api_error::{ApiError, ApiJson};
+use crate::api_openapi::openapi_document;
+use crate::apps::{find_available_port, get_registered_apps, register_app, unregister_app};
+use crate::checkpoints::{
+    cleanup_checkpoints, create_checkpoint, diff_checkpoints, list_checkpoints,
+    restore_checkpoint, restore_checkpoint_paths, validate_id, CheckpointDiff, CheckpointResult,
+    CheckpointSummary, CleanupResult, RestoreResult,
+};
 use crate::paths::{
     get_config_file_path_for_workspace, get_moldable_root, get_shared_apps_dir, get_workspace_dir,
     get_workspaces_config_internal,
 };
 use crate::ports::{acquire_port, PortAcquisitionConfig, DEFAULT_API_SERVER_PORT};
+use crate::process::{
+    get_app_logs_internal, get_app_status_internal, start_registered_app, stop_app_internal,
+    AppState,
+};
 use crate::registry::uninstall_app_from_shared;
 use crate::runtime::ensure_node_modules_installed;
-use crate::types::{MoldableConfig, RegisteredApp};
+use crate::types::{AppStatus, MoldableConfig, RegisteredApp, WorkspacesConfig};
 use crate::workspace::copy_dir_recursive;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path as RoutePath, State},
+    middleware,
     routing::{get, post},
     Json, Router,
 };
 use log::{error, info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tauri::Manager;
 use tokio::sync::Mutex;
 
 /// Default port for the API server (re-export from ports for external use)
@@ -43,6 +60,9 @@ const _: () = {
     assert!(API_SERVER_FALLBACK_END < 65535);
 };
 
+/// Result of an API handler
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
 // ============================================================================
 // TYPES
 // ============================================================================
@@ -52,7 +72,13 @@ pub struct ApiState {
     pub app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub service: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateAppRequest {
     /// Unique app identifier (lowercase, hyphens allowed)
@@ -79,125 +105,235 @@ fn default_widget_size() -> String {
     "medium".to_string()
 }
 
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAppResponse {
+    pub success: bool,
+    pub app_id: String,
+    pub name: String,
+    pub icon: String,
+    pub port: u16,
+    pub path: String,
+    pub files: Vec<String>,
+    pub pnpm_installed: bool,
+    pub registered: bool,
+    pub message: String,
+}
+
 // ============================================================================
 // APP MANAGEMENT TYPES
 // ============================================================================
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct UnregisterAppRequest {
     pub app_id: String,
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct UnregisterAppResponse {
     pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
+    pub app_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub app_name: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteAppDataRequest {
     pub app_id: String,
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteAppDataResponse {
     pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub deleted_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub app_id: String,
+    pub deleted_path: String,
+    pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteAppRequest {
     pub app_id: String,
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteAppResponse {
     pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
+    pub app_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub app_name: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub deleted_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub workspaces_affected: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub deleted_path: String,
+    pub workspaces_affected: Vec<String>,
+    pub message: String,
 }
 
 /// Info about app installation across workspaces (for pre-flight check)
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AppInfoRequest {
     pub app_id: String,
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AppInfoResponse {
     pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_name: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_path: Option<String>,
+    pub app_id: String,
+    pub app_name: String,
+    pub app_path: String,
     /// List of workspace names where this app is installed
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub installed_in_workspaces: Option<Vec<String>>,
+    pub installed_in_workspaces: Vec<String>,
     /// Whether the app has data in the current workspace
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub has_workspace_data: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub has_workspace_data: bool,
 }
 
-#[derive(Debug, Serialize, Default)]
+// ============================================================================
+// APP PROCESS TYPES
+// ============================================================================
+
+/// Process state of an app, returned by the start/stop/restart/status endpoints
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct CreateAppResponse {
+pub struct AppProcessResponse {
     pub success: bool,
+    pub app_id: String,
+    pub running: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub name: Option<String>,
+    pub pid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub icon: Option<String>,
+    pub exit_code: Option<i32>,
+    /// The port the app is actually listening on
     #[serde(skip_serializing_if = "Option::is_none")]
     pub port: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub files: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub pnpm_installed: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub registered: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub recent_output: Vec<String>,
+}
+
+impl AppProcessResponse {
+    fn from_status(app_id: String, status: AppStatus) -> Self {
+        Self {
+            success: true,
+            app_id,
+            running: status.running,
+            pid: status.pid,
+            exit_code: status.exit_code,
+            port: status.actual_port,
+            recent_output: status.recent_output,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLogsResponse {
+    pub success: bool,
+    pub app_id: String,
+    /// Install progress followed by the app's captured stdout/stderr
+    pub lines: Vec<String>,
+}
+
+// ============================================================================
+// CHECKPOINT TYPES
+// ============================================================================
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCheckpointRequest {
+    /// Registered app whose directory is captured
+    pub app_id: String,
+    pub conversation_id: String,
+    pub message_id: String,
+    /// Tells apart several checkpoints of the same message
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Re-hash every file instead of trusting the stat cache
+    #[serde(default)]
+    pub full_hash: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCheckpointsRequest {
+    pub app_id: String,
+    pub conversation_id: String,
+    #[serde(default)]
+    pub full_hash: Option<bool>,
+}
+
+/// Diff two checkpoints, or one checkpoint against the working tree when the
+/// `to` side is omitted
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffCheckpointsRequest {
+    pub app_id: String,
+    pub conversation_id: String,
+    #[serde(default)]
+    pub from_message_id: Option<String>,
+    #[serde(default)]
+    pub from_snapshot_id: Option<String>,
+    #[serde(default)]
+    pub to_message_id: Option<String>,
+    #[serde(default)]
+    pub to_snapshot_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreCheckpointRequest {
+    pub app_id: String,
+    pub conversation_id: String,
+    /// Restore the latest checkpoint of this message
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Restore this checkpoint; takes precedence over `message_id`
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    /// Only restore these paths or glob patterns (everything if empty)
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupCheckpointsRequest {
+    pub app_id: String,
+    pub conversation_id: String,
+    #[serde(default = "default_keep_last_n")]
+    pub keep_last_n: usize,
+}
+
+fn default_keep_last_n() -> usize {
+    50
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointList {
+    pub checkpoints: Vec<CheckpointSummary>,
+}
+
+/// Response of the checkpoint endpoints: the result's fields next to `success`
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(rename = "{T}Response")]
+pub struct CheckpointResponse<T> {
+    pub success: bool,
+    #[serde(flatten)]
+    pub result: T,
+}
+
+impl<T> CheckpointResponse<T> {
+    fn ok(result: T) -> Self {
+        Self {
+            success: true,
+            result,
+        }
+    }
 }
 
 // ============================================================================
@@ -328,45 +464,33 @@ fn add_dependencies_to_package_json(
 // HANDLERS
 // ============================================================================
 
-async fn health_handler() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "ok",
-        "service": "moldable-api"
-    }))
+async fn health_handler() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        service: "moldable-api".to_string(),
+    })
+}
+
+/// Serve the OpenAPI description of this server
+async fn openapi_handler() -> Json<serde_json::Value> {
+    Json(openapi_document())
 }
 
 async fn create_app_handler(
     State(state): State<ApiState>,
-    Json(req): Json<CreateAppRequest>,
-) -> impl IntoResponse {
+    ApiJson(req): ApiJson<CreateAppRequest>,
+) -> ApiResult<CreateAppResponse> {
     info!("Creating app: {} ({})", req.name, req.app_id);
 
     // Validate app_id format
     if !req.app_id.chars().all(|c| c.is_ascii_lowercase() || c == '-' || c.is_ascii_digit()) {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some("App ID must be lowercase with hyphens and numbers only".to_string()),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::invalid_request(
+            "App ID must be lowercase with hyphens and numbers only",
+        ));
     }
 
     // Get paths
-    let moldable_root = match get_moldable_root() {
-        Ok(root) => root,
-        Err(err) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(CreateAppResponse {
-                    success: false,
-                    error: Some(err),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+    let moldable_root = get_moldable_root().map_err(ApiError::internal)?;
 
     info!("Creating app with MOLDABLE_HOME={}", moldable_root.display());
 
@@ -376,58 +500,28 @@ async fn create_app_handler(
 
     // Check if template exists
     if !template_path.exists() {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some(format!(
-                    "App template not found at {}. Restart Moldable to install it.",
-                    template_path.display()
-                )),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::unavailable(format!(
+            "App template not found at {}. Restart Moldable to install it.",
+            template_path.display()
+        )));
     }
 
     // Check if app already exists
     if dest_dir.exists() {
-        return (
-            StatusCode::CONFLICT,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some(format!(
-                    "App '{}' already exists at {}",
-                    req.app_id,
-                    dest_dir.display()
-                )),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::conflict(format!(
+            "App '{}' already exists at {}",
+            req.app_id,
+            dest_dir.display()
+        )));
     }
 
     // Ensure apps directory exists
-    if let Err(e) = fs::create_dir_all(&apps_dir) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some(format!("Failed to create apps directory: {}", e)),
-                ..Default::default()
-            }),
-        );
-    }
+    fs::create_dir_all(&apps_dir)
+        .map_err(|e| ApiError::internal(format!("Failed to create apps directory: {}", e)))?;
 
     // Copy template to destination
-    if let Err(e) = copy_dir_recursive(&template_path, &dest_dir) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some(format!("Failed to copy template: {}", e)),
-                ..Default::default()
-            }),
-        );
-    }
+    copy_dir_recursive(&template_path, &dest_dir)
+        .map_err(|e| ApiError::internal(format!("Failed to copy template: {}", e)))?;
 
     // Replace placeholders
     let mut replacements: HashMap<&str, &str> = HashMap::new();
@@ -440,14 +534,7 @@ async fn create_app_handler(
     if let Err(e) = replace_placeholders_recursive(&dest_dir, &replacements) {
         // Cleanup on failure
         let _ = fs::remove_dir_all(&dest_dir);
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(CreateAppResponse {
-                success: false,
-                error: Some(format!("Failed to replace placeholders: {}", e)),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::internal(format!("Failed to replace placeholders: {}", e)));
     }
 
     // Add extra dependencies if provided
@@ -520,26 +607,22 @@ async fn create_app_handler(
         req.app_id, port, pnpm_installed, registered
     );
 
-    (
-        StatusCode::OK,
-        Json(CreateAppResponse {
-            success: true,
-            app_id: Some(req.app_id),
-            name: Some(req.name),
-            icon: Some(req.icon),
-            port: Some(port),
-            path: Some(dest_dir.to_string_lossy().to_string()),
-            files: Some(files),
-            pnpm_installed: Some(pnpm_installed),
-            registered: Some(registered),
-            message: Some(format!(
-                "App created and {} on port {}",
-                if registered { "registered" } else { "ready to register" },
-                port
-            )),
-            error: None,
-        }),
-    )
+    Ok(Json(CreateAppResponse {
+        success: true,
+        app_id: req.app_id,
+        name: req.name,
+        icon: req.icon,
+        port,
+        path: dest_dir.to_string_lossy().to_string(),
+        files,
+        pnpm_installed,
+        registered,
+        message: format!(
+            "App created and {} on port {}",
+            if registered { "registered" } else { "ready to register" },
+            port
+        ),
+    }))
 }
 
 fn collect_created_files(dir: &PathBuf) -> Vec<String> {
@@ -570,56 +653,49 @@ fn collect_created_files(dir: &PathBuf) -> Vec<String> {
 // APP MANAGEMENT HANDLERS
 // ============================================================================
 
-/// Get app info including which workspaces it's installed in
-async fn get_app_info_handler(
-    Json(req): Json<AppInfoRequest>,
-) -> impl IntoResponse {
-    info!("Getting app info for: {}", req.app_id);
+/// The desktop app's handle, which registry and workspace changes go through
+async fn app_handle(state: &ApiState) -> Result<tauri::AppHandle, ApiError> {
+    state
+        .app_handle
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| ApiError::unavailable("No app handle available"))
+}
 
-    // Get shared apps directory
-    let shared_apps_dir = match get_shared_apps_dir() {
-        Ok(dir) => dir,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(AppInfoResponse {
-                    success: false,
-                    error: Some(format!("Failed to get shared apps dir: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+/// Directory of an installed app in shared/apps
+fn shared_app_dir(app_id: &str) -> Result<PathBuf, ApiError> {
+    let shared_apps_dir = get_shared_apps_dir()
+        .map_err(|e| ApiError::internal(format!("Failed to get shared apps dir: {}", e)))?;
 
-    let app_dir = shared_apps_dir.join(&req.app_id);
+    let app_dir = shared_apps_dir.join(app_id);
     if !app_dir.exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(AppInfoResponse {
-                success: false,
-                error: Some(format!("App '{}' not found in shared apps", req.app_id)),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::not_found(format!(
+            "App '{}' not found in shared apps",
+            app_id
+        )));
     }
+    Ok(app_dir)
+}
+
+fn workspaces_config() -> Result<WorkspacesConfig, ApiError> {
+    get_workspaces_config_internal()
+        .map_err(|e| ApiError::internal(format!("Failed to get workspaces config: {}", e)))
+}
+
+/// Get app info including which workspaces it's installed in
+async fn get_app_info_handler(
+    ApiJson(req): ApiJson<AppInfoRequest>,
+) -> ApiResult<AppInfoResponse> {
+    info!("Getting app info for: {}", req.app_id);
+
+    let app_dir = shared_app_dir(&req.app_id)?;
 
     // Try to get app name from moldable.json
     let app_name = get_app_name_from_manifest(&app_dir).unwrap_or_else(|| req.app_id.clone());
 
     // Get workspaces config to find all workspaces
-    let workspaces_config = match get_workspaces_config_internal() {
-        Ok(config) => config,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(AppInfoResponse {
-                    success: false,
-                    error: Some(format!("Failed to get workspaces config: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+    let workspaces_config = workspaces_config()?;
 
     // Check which workspaces have this app registered
     let mut installed_workspaces = Vec::new();
@@ -645,214 +721,91 @@ async fn get_app_info_handler(
         false
     };
 
-    (
-        StatusCode::OK,
-        Json(AppInfoResponse {
-            success: true,
-            app_id: Some(req.app_id),
-            app_name: Some(app_name),
-            app_path: Some(app_dir.to_string_lossy().to_string()),
-            installed_in_workspaces: Some(installed_workspaces),
-            has_workspace_data: Some(has_workspace_data),
-            error: None,
-        }),
-    )
+    Ok(Json(AppInfoResponse {
+        success: true,
+        app_id: req.app_id,
+        app_name,
+        app_path: app_dir.to_string_lossy().to_string(),
+        installed_in_workspaces: installed_workspaces,
+        has_workspace_data,
+    }))
 }
 
 /// Unregister an app from the current workspace only (keeps code and data)
 async fn unregister_app_handler(
     State(state): State<ApiState>,
-    Json(req): Json<UnregisterAppRequest>,
-) -> impl IntoResponse {
+    ApiJson(req): ApiJson<UnregisterAppRequest>,
+) -> ApiResult<UnregisterAppResponse> {
     info!("Unregistering app from workspace: {}", req.app_id);
 
-    let app_handle = {
-        let guard = state.app_handle.lock().await;
-        guard.clone()
-    };
-
-    let Some(handle) = app_handle else {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(UnregisterAppResponse {
-                success: false,
-                error: Some("No app handle available".to_string()),
-                ..Default::default()
-            }),
-        );
-    };
+    let handle = app_handle(&state).await?;
 
     // Get app name before unregistering (for display purposes)
     let app_name = get_current_workspace_app_name(&req.app_id);
 
-    match unregister_app(handle, req.app_id.clone()) {
-        Ok(_) => {
-            info!("Unregistered {} from workspace", req.app_id);
-            (
-                StatusCode::OK,
-                Json(UnregisterAppResponse {
-                    success: true,
-                    app_id: Some(req.app_id),
-                    app_name,
-                    message: Some("App removed from workspace. Code and data are preserved.".to_string()),
-                    error: None,
-                }),
-            )
-        }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(UnregisterAppResponse {
-                success: false,
-                error: Some(format!("Failed to unregister app: {}", e)),
-                ..Default::default()
-            }),
-        ),
-    }
+    unregister_app(handle, req.app_id.clone())
+        .map_err(|e| ApiError::internal(format!("Failed to unregister app: {}", e)))?;
+
+    info!("Unregistered {} from workspace", req.app_id);
+    Ok(Json(UnregisterAppResponse {
+        success: true,
+        app_id: req.app_id,
+        app_name,
+        message: "App removed from workspace. Code and data are preserved.".to_string(),
+    }))
 }
 
 /// Delete app data for the current workspace only (keeps app registered)
 async fn delete_app_data_handler(
-    Json(req): Json<DeleteAppDataRequest>,
-) -> impl IntoResponse {
+    ApiJson(req): ApiJson<DeleteAppDataRequest>,
+) -> ApiResult<DeleteAppDataResponse> {
     info!("Deleting app data for: {}", req.app_id);
 
     // Get the current workspace's app data directory
-    let workspaces_config = match get_workspaces_config_internal() {
-        Ok(config) => config,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(DeleteAppDataResponse {
-                    success: false,
-                    error: Some(format!("Failed to get workspaces config: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+    let workspaces_config = workspaces_config()?;
 
-    let workspace_dir = match get_workspace_dir(&workspaces_config.active_workspace) {
-        Ok(dir) => dir,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(DeleteAppDataResponse {
-                    success: false,
-                    error: Some(format!("Failed to get workspace dir: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+    let workspace_dir = get_workspace_dir(&workspaces_config.active_workspace)
+        .map_err(|e| ApiError::internal(format!("Failed to get workspace dir: {}", e)))?;
 
     let app_data_dir = workspace_dir.join("apps").join(&req.app_id);
 
     if !app_data_dir.exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(DeleteAppDataResponse {
-                success: false,
-                error: Some(format!("No data found for app '{}' in this workspace", req.app_id)),
-                ..Default::default()
-            }),
-        );
+        return Err(ApiError::not_found(format!(
+            "No data found for app '{}' in this workspace",
+            req.app_id
+        )));
     }
 
     let deleted_path = app_data_dir.to_string_lossy().to_string();
 
-    match fs::remove_dir_all(&app_data_dir) {
-        Ok(_) => {
-            info!("Deleted app data at: {}", deleted_path);
-            (
-                StatusCode::OK,
-                Json(DeleteAppDataResponse {
-                    success: true,
-                    app_id: Some(req.app_id),
-                    deleted_path: Some(deleted_path),
-                    message: Some("App data deleted. The app is still installed and will start fresh.".to_string()),
-                    error: None,
-                }),
-            )
-        }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DeleteAppDataResponse {
-                success: false,
-                error: Some(format!("Failed to delete app data: {}", e)),
-                ..Default::default()
-            }),
-        ),
-    }
+    fs::remove_dir_all(&app_data_dir)
+        .map_err(|e| ApiError::internal(format!("Failed to delete app data: {}", e)))?;
+
+    info!("Deleted app data at: {}", deleted_path);
+    Ok(Json(DeleteAppDataResponse {
+        success: true,
+        app_id: req.app_id,
+        deleted_path,
+        message: "App data deleted. The app is still installed and will start fresh.".to_string(),
+    }))
 }
 
 /// Delete an app completely (removes from all workspaces, deletes code and data)
 async fn delete_app_handler(
     State(state): State<ApiState>,
-    Json(req): Json<DeleteAppRequest>,
-) -> impl IntoResponse {
+    ApiJson(req): ApiJson<DeleteAppRequest>,
+) -> ApiResult<DeleteAppResponse> {
     info!("Deleting app completely: {}", req.app_id);
 
-    let app_handle = {
-        let guard = state.app_handle.lock().await;
-        guard.clone()
-    };
-
-    let Some(handle) = app_handle else {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DeleteAppResponse {
-                success: false,
-                error: Some("No app handle available".to_string()),
-                ..Default::default()
-            }),
-        );
-    };
-
-    // Get shared apps directory
-    let shared_apps_dir = match get_shared_apps_dir() {
-        Ok(dir) => dir,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(DeleteAppResponse {
-                    success: false,
-                    error: Some(format!("Failed to get shared apps dir: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
-
-    let app_dir = shared_apps_dir.join(&req.app_id);
-    if !app_dir.exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(DeleteAppResponse {
-                success: false,
-                error: Some(format!("App '{}' not found in shared apps", req.app_id)),
-                ..Default::default()
-            }),
-        );
-    }
+    let handle = app_handle(&state).await?;
+    let app_dir = shared_app_dir(&req.app_id)?;
 
     // Get app name before deleting
     let app_name = get_app_name_from_manifest(&app_dir);
     let deleted_path = app_dir.to_string_lossy().to_string();
 
     // Get all workspaces to unregister from and delete data
-    let workspaces_config = match get_workspaces_config_internal() {
-        Ok(config) => config,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(DeleteAppResponse {
-                    success: false,
-                    error: Some(format!("Failed to get workspaces config: {}", e)),
-                    ..Default::default()
-                }),
-            );
-        }
-    };
+    let workspaces_config = workspaces_config()?;
 
     let mut affected_workspaces = Vec::new();
 
@@ -884,31 +837,269 @@ async fn delete_app_handler(
     }
 
     // Use the existing uninstall_app_from_shared which handles unregistering + deleting code
-    match uninstall_app_from_shared(handle, req.app_id.clone()) {
-        Ok(_) => {
-            info!("Deleted app {} completely", req.app_id);
-            (
-                StatusCode::OK,
-                Json(DeleteAppResponse {
-                    success: true,
-                    app_id: Some(req.app_id),
-                    app_name,
-                    deleted_path: Some(deleted_path),
-                    workspaces_affected: Some(affected_workspaces),
-                    message: Some("App deleted permanently from all workspaces.".to_string()),
-                    error: None,
-                }),
-            )
+    uninstall_app_from_shared(handle, req.app_id.clone())
+        .map_err(|e| ApiError::internal(format!("Failed to delete app: {}", e)))?;
+
+    info!("Deleted app {} completely", req.app_id);
+    Ok(Json(DeleteAppResponse {
+        success: true,
+        app_id: req.app_id,
+        app_name,
+        deleted_path,
+        workspaces_affected: affected_workspaces,
+        message: "App deleted permanently from all workspaces.".to_string(),
+    }))
+}
+
+// ============================================================================
+// APP PROCESS HANDLERS
+// ============================================================================
+
+/// Run a blocking process operation against the desktop app's `AppState`, so
+/// API callers and the UI see the same running processes
+async fn with_app_state<T, F>(state: &ApiState, op: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppState) -> Result<T, String> + Send + 'static,
+{
+    let handle = app_handle(state).await?;
+    let app_state = AppState(handle.state::<AppState>().inner().0.clone());
+
+    tokio::task::spawn_blocking(move || op(&app_state))
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to run app process task: {}", e)))?
+        .map_err(ApiError::internal)
+}
+
+/// The registered app with `app_id` in the active workspace, if any
+async fn find_registered_app(app_id: &str) -> Result<Option<RegisteredApp>, ApiError> {
+    let app_id = app_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        get_registered_apps().map(|apps| apps.into_iter().find(|app| app.id == app_id))
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("Failed to read registered apps: {}", e)))?
+    .map_err(ApiError::internal)
+}
+
+/// The registered app with `app_id`, or a not-found error
+async fn registered_app(app_id: &str) -> Result<RegisteredApp, ApiError> {
+    find_registered_app(app_id).await?.ok_or_else(|| {
+        ApiError::not_found(format!("App '{}' is not registered in this workspace", app_id))
+    })
+}
+
+/// Shared body of the start/stop/restart/status handlers
+async fn app_process_response(
+    state: ApiState,
+    app_id: String,
+    action: fn(&str, &AppState) -> Result<AppStatus, String>,
+) -> ApiResult<AppProcessResponse> {
+    registered_app(&app_id).await?;
+
+    let id = app_id.clone();
+    let status = with_app_state(&state, move |app_state| action(&id, app_state)).await?;
+    Ok(Json(AppProcessResponse::from_status(app_id, status)))
+}
+
+/// Start an app with its registered command (no-op if it's already running)
+async fn start_app_handler(
+    State(state): State<ApiState>,
+    RoutePath(app_id): RoutePath<String>,
+) -> ApiResult<AppProcessResponse> {
+    info!("Starting app via API: {}", app_id);
+    app_process_response(state, app_id, start_registered_app).await
+}
+
+/// Stop an app and its whole process tree
+async fn stop_app_handler(
+    State(state): State<ApiState>,
+    RoutePath(app_id): RoutePath<String>,
+) -> ApiResult<AppProcessResponse> {
+    info!("Stopping app via API: {}", app_id);
+    app_process_response(state, app_id, stop_app_internal).await
+}
+
+/// Stop an app (if running) and start it again
+async fn restart_app_handler(
+    State(state): State<ApiState>,
+    RoutePath(app_id): RoutePath<String>,
+) -> ApiResult<AppProcessResponse> {
+    info!("Restarting app via API: {}", app_id);
+    app_process_response(state, app_id, |app_id, app_state| {
+        stop_app_internal(app_id, app_state)?;
+        start_registered_app(app_id, app_state)
+    })
+    .await
+}
+
+/// Get whether an app is running, plus its recent output
+async fn app_status_handler(
+    State(state): State<ApiState>,
+    RoutePath(app_id): RoutePath<String>,
+) -> ApiResult<AppProcessResponse> {
+    app_process_response(state, app_id, |app_id, app_state| {
+        get_app_status_internal(app_id.to_string(), app_state)
+    })
+    .await
+}
+
+/// Get an app's install progress and captured output (of the last run if stopped)
+async fn app_logs_handler(
+    State(state): State<ApiState>,
+    RoutePath(app_id): RoutePath<String>,
+) -> ApiResult<AppLogsResponse> {
+    registered_app(&app_id).await?;
+
+    let id = app_id.clone();
+    let lines =
+        with_app_state(&state, move |app_state| get_app_logs_internal(&id, app_state)).await?;
+    Ok(Json(AppLogsResponse {
+        success: true,
+        app_id,
+        lines,
+    }))
+}
+
+// ============================================================================
+// CHECKPOINT HANDLERS
+// ============================================================================
+
+/// Validate the ids a checkpoint request turns into paths, then run `op` on a
+/// blocking thread
+async fn checkpoint_response<T, F>(
+    ids: Vec<(&'static str, String)>,
+    op: F,
+) -> ApiResult<CheckpointResponse<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    for (kind, id) in &ids {
+        validate_id(kind, id).map_err(ApiError::invalid_request)?;
+    }
+
+    let result = tokio::task::spawn_blocking(op)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to run checkpoint task: {}", e)))?
+        .map_err(ApiError::internal)?;
+    Ok(Json(CheckpointResponse::ok(result)))
+}
+
+/// Capture a checkpoint of a registered app's directory
+async fn create_checkpoint_handler(
+    ApiJson(req): ApiJson<CreateCheckpointRequest>,
+) -> ApiResult<CheckpointResponse<CheckpointResult>> {
+    info!(
+        "Creating checkpoint for {} (conversation {}, message {})",
+        req.app_id, req.conversation_id, req.message_id
+    );
+
+    // Only registered apps can be captured, so callers can't snapshot (and
+    // later restore into) arbitrary directories
+    let app_dir = registered_app(&req.app_id).await?.path;
+
+    let ids = vec![
+        ("app ID", req.app_id.clone()),
+        ("conversation ID", req.conversation_id.clone()),
+        ("message ID", req.message_id.clone()),
+    ];
+    checkpoint_response(ids, move || {
+        tauri::async_runtime::block_on(create_checkpoint(
+            req.app_id,
+            app_dir,
+            req.conversation_id,
+            req.message_id,
+            req.full_hash,
+            req.label,
+        ))
+    })
+    .await
+}
+
+/// List the checkpoints of a conversation
+async fn list_checkpoints_handler(
+    ApiJson(req): ApiJson<ListCheckpointsRequest>,
+) -> ApiResult<CheckpointResponse<CheckpointList>> {
+    let ids = vec![
+        ("app ID", req.app_id.clone()),
+        ("conversation ID", req.conversation_id.clone()),
+    ];
+    checkpoint_response(ids, move || {
+        list_checkpoints(req.app_id, req.conversation_id, req.full_hash)
+            .map(|checkpoints| CheckpointList { checkpoints })
+    })
+    .await
+}
+
+/// Diff a checkpoint against another one or the working tree
+async fn diff_checkpoints_handler(
+    ApiJson(req): ApiJson<DiffCheckpointsRequest>,
+) -> ApiResult<CheckpointResponse<CheckpointDiff>> {
+    let ids = vec![
+        ("app ID", req.app_id.clone()),
+        ("conversation ID", req.conversation_id.clone()),
+    ];
+    checkpoint_response(ids, move || {
+        diff_checkpoints(
+            req.app_id,
+            req.conversation_id,
+            req.from_message_id,
+            req.to_message_id,
+            req.from_snapshot_id,
+            req.to_snapshot_id,
+        )
+    })
+    .await
+}
+
+/// Restore all files, or only `paths`, to a checkpoint. The response carries
+/// the pre-restore snapshot that undoes it.
+async fn restore_checkpoint_handler(
+    ApiJson(req): ApiJson<RestoreCheckpointRequest>,
+) -> ApiResult<CheckpointResponse<RestoreResult>> {
+    info!(
+        "Restoring checkpoint for {} (conversation {})",
+        req.app_id, req.conversation_id
+    );
+
+    let ids = vec![
+        ("app ID", req.app_id.clone()),
+        ("conversation ID", req.conversation_id.clone()),
+    ];
+    checkpoint_response(ids, move || {
+        if req.paths.is_empty() {
+            tauri::async_runtime::block_on(restore_checkpoint(
+                req.app_id,
+                req.conversation_id,
+                req.message_id,
+                req.snapshot_id,
+            ))
+        } else {
+            tauri::async_runtime::block_on(restore_checkpoint_paths(
+                req.app_id,
+                req.conversation_id,
+                req.message_id,
+                req.paths,
+                req.snapshot_id,
+            ))
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DeleteAppResponse {
-                success: false,
-                error: Some(format!("Failed to delete app: {}", e)),
-                ..Default::default()
-            }),
-        ),
-    }
+    })
+    .await
+}
+
+/// Delete all but the last `keepLastN` checkpoints of a conversation
+async fn cleanup_checkpoints_handler(
+    ApiJson(req): ApiJson<CleanupCheckpointsRequest>,
+) -> ApiResult<CheckpointResponse<CleanupResult>> {
+    let ids = vec![
+        ("app ID", req.app_id.clone()),
+        ("conversation ID", req.conversation_id.clone()),
+    ];
+    checkpoint_response(ids, move || {
+        cleanup_checkpoints(req.app_id, req.conversation_id, req.keep_last_n)
+    })
+    .await
 }
 
 /// Helper to get app name from moldable.json manifest
@@ -972,12 +1163,25 @@ pub async fn start_api_server(app_handle: tauri::AppHandle) -> Result<u16, Strin
     };
 
     let app = Router::new()
+        // Keep in sync with the operations listed in `api_openapi`
         .route("/health", get(health_handler))
+        .route("/api/openapi.json", get(openapi_handler))
         .route("/api/create-app", post(create_app_handler))
         .route("/api/app-info", post(get_app_info_handler))
         .route("/api/unregister-app", post(unregister_app_handler))
         .route("/api/delete-app-data", post(delete_app_data_handler))
         .route("/api/delete-app", post(delete_app_handler))
+        .route("/api/apps/{id}/start", post(start_app_handler))
+        .route("/api/apps/{id}/stop", post(stop_app_handler))
+        .route("/api/apps/{id}/restart", post(restart_app_handler))
+        .route("/api/apps/{id}/status", get(app_status_handler))
+        .route("/api/apps/{id}/logs", get(app_logs_handler))
+        .route("/api/checkpoints/create", post(create_checkpoint_handler))
+        .route("/api/checkpoints/list", post(list_checkpoints_handler))
+        .route("/api/checkpoints/diff", post(diff_checkpoints_handler))
+        .route("/api/checkpoints/restore", post(restore_checkpoint_handler))
+        .route("/api/checkpoints/cleanup", post(cleanup_checkpoints_handler))
+        .layer(middleware::from_fn(require_api_auth))
         .with_state(state);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], actual_port));
@@ -1009,6 +1213,7 @@ pub async fn start_api_server(app_handle: tauri::AppHandle) -> Result<u16, Strin
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api_error::ApiErrorCode;
     use tempfile::TempDir;
 
     #[test]
@@ -1016,14 +1221,6 @@ mod tests {
         assert_eq!(default_widget_size(), "medium");
     }
 
-    #[test]
-    fn test_create_app_response_default() {
-        let response = CreateAppResponse::default();
-        assert!(!response.success);
-        assert!(response.app_id.is_none());
-        assert!(response.error.is_none());
-    }
-
     // ==================== PORT CONSTANTS TESTS ====================
     
     #[test]
@@ -1320,23 +1517,23 @@ mod tests {
     fn test_create_app_response_serialization() {
         let response = CreateAppResponse {
             success: true,
-            app_id: Some("test-app".to_string()),
-            name: Some("Test App".to_string()),
-            icon: Some("🚀".to_string()),
-            port: Some(4100),
-            path: Some("/path/to/app".to_string()),
-            files: Some(vec!["file1.txt".to_string()]),
-            pnpm_installed: Some(true),
-            registered: Some(true),
-            message: Some("App created".to_string()),
-            error: None,
+            app_id: "test-app".to_string(),
+            name: "Test App".to_string(),
+            icon: "🚀".to_string(),
+            port: 4100,
+            path: "/path/to/app".to_string(),
+            files: vec!["file1.txt".to_string()],
+            pnpm_installed: true,
+            registered: true,
+            message: "App created".to_string(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
         assert!(json.contains("\"success\":true"));
         assert!(json.contains("\"appId\":\"test-app\""));
         assert!(json.contains("\"port\":4100"));
-        assert!(!json.contains("error")); // None fields should be skipped
+        assert!(json.contains("\"pnpmInstalled\":true"));
+        assert!(!json.contains("error"));
     }
 
     // ==================== APP MANAGEMENT REQUEST/RESPONSE TESTS ====================
@@ -1348,24 +1545,13 @@ mod tests {
         assert_eq!(req.app_id, "my-app");
     }
 
-    #[test]
-    fn test_unregister_app_response_default() {
-        let response = UnregisterAppResponse::default();
-        assert!(!response.success);
-        assert!(response.app_id.is_none());
-        assert!(response.app_name.is_none());
-        assert!(response.message.is_none());
-        assert!(response.error.is_none());
-    }
-
     #[test]
     fn test_unregister_app_response_serialization() {
         let response = UnregisterAppResponse {
             success: true,
-            app_id: Some("my-app".to_string()),
+            app_id: "my-app".to_string(),
             app_name: Some("My App".to_string()),
-            message: Some("Removed from workspace".to_string()),
-            error: None,
+            message: "Removed from workspace".to_string(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -1373,7 +1559,20 @@ mod tests {
         assert!(json.contains("\"appId\":\"my-app\""));
         assert!(json.contains("\"appName\":\"My App\""));
         assert!(json.contains("\"message\""));
-        assert!(!json.contains("error")); // None fields should be skipped
+        assert!(!json.contains("error"));
+    }
+
+    #[test]
+    fn test_unregister_app_response_skips_missing_name() {
+        let response = UnregisterAppResponse {
+            success: true,
+            app_id: "my-app".to_string(),
+            app_name: None,
+            message: "Removed from workspace".to_string(),
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(!json.contains("appName"));
     }
 
     #[test]
@@ -1383,24 +1582,13 @@ mod tests {
         assert_eq!(req.app_id, "test-app");
     }
 
-    #[test]
-    fn test_delete_app_data_response_default() {
-        let response = DeleteAppDataResponse::default();
-        assert!(!response.success);
-        assert!(response.app_id.is_none());
-        assert!(response.deleted_path.is_none());
-        assert!(response.message.is_none());
-        assert!(response.error.is_none());
-    }
-
     #[test]
     fn test_delete_app_data_response_serialization() {
         let response = DeleteAppDataResponse {
             success: true,
-            app_id: Some("test-app".to_string()),
-            deleted_path: Some("/path/to/data".to_string()),
-            message: Some("Data deleted".to_string()),
-            error: None,
+            app_id: "test-app".to_string(),
+            deleted_path: "/path/to/data".to_string(),
+            message: "Data deleted".to_string(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -1417,28 +1605,15 @@ mod tests {
         assert_eq!(req.app_id, "scribo");
     }
 
-    #[test]
-    fn test_delete_app_response_default() {
-        let response = DeleteAppResponse::default();
-        assert!(!response.success);
-        assert!(response.app_id.is_none());
-        assert!(response.app_name.is_none());
-        assert!(response.deleted_path.is_none());
-        assert!(response.workspaces_affected.is_none());
-        assert!(response.message.is_none());
-        assert!(response.error.is_none());
-    }
-
     #[test]
     fn test_delete_app_response_serialization() {
         let response = DeleteAppResponse {
             success: true,
-            app_id: Some("scribo".to_string()),
+            app_id: "scribo".to_string(),
             app_name: Some("Scribo Languages".to_string()),
-            deleted_path: Some("/Users/rob/.moldable/shared/apps/scribo".to_string()),
-            workspaces_affected: Some(vec!["Personal".to_string(), "Work".to_string()]),
-            message: Some("App deleted permanently".to_string()),
-            error: None,
+            deleted_path: "/Users/rob/.moldable/shared/apps/scribo".to_string(),
+            workspaces_affected: vec!["Personal".to_string(), "Work".to_string()],
+            message: "App deleted permanently".to_string(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -1452,25 +1627,6 @@ mod tests {
         assert!(!json.contains("error"));
     }
 
-    #[test]
-    fn test_delete_app_response_with_error() {
-        let response = DeleteAppResponse {
-            success: false,
-            app_id: None,
-            app_name: None,
-            deleted_path: None,
-            workspaces_affected: None,
-            message: None,
-            error: Some("App not found".to_string()),
-        };
-
-        let json = serde_json::to_string(&response).unwrap();
-        assert!(json.contains("\"success\":false"));
-        assert!(json.contains("\"error\":\"App not found\""));
-        assert!(!json.contains("appId")); // None fields should be skipped
-        assert!(!json.contains("workspacesAffected"));
-    }
-
     #[test]
     fn test_app_info_request_deserialization() {
         let json = r#"{"appId": "calendar"}"#;
@@ -1478,28 +1634,15 @@ mod tests {
         assert_eq!(req.app_id, "calendar");
     }
 
-    #[test]
-    fn test_app_info_response_default() {
-        let response = AppInfoResponse::default();
-        assert!(!response.success);
-        assert!(response.app_id.is_none());
-        assert!(response.app_name.is_none());
-        assert!(response.app_path.is_none());
-        assert!(response.installed_in_workspaces.is_none());
-        assert!(response.has_workspace_data.is_none());
-        assert!(response.error.is_none());
-    }
-
     #[test]
     fn test_app_info_response_serialization() {
         let response = AppInfoResponse {
             success: true,
-            app_id: Some("calendar".to_string()),
-            app_name: Some("Calendar".to_string()),
-            app_path: Some("/Users/rob/.moldable/shared/apps/calendar".to_string()),
-            installed_in_workspaces: Some(vec!["Personal".to_string(), "Work".to_string()]),
-            has_workspace_data: Some(true),
-            error: None,
+            app_id: "calendar".to_string(),
+            app_name: "Calendar".to_string(),
+            app_path: "/Users/rob/.moldable/shared/apps/calendar".to_string(),
+            installed_in_workspaces: vec!["Personal".to_string(), "Work".to_string()],
+            has_workspace_data: true,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -1518,12 +1661,11 @@ mod tests {
     fn test_app_info_response_empty_workspaces() {
         let response = AppInfoResponse {
             success: true,
-            app_id: Some("orphan-app".to_string()),
-            app_name: Some("Orphan App".to_string()),
-            app_path: Some("/path/to/app".to_string()),
-            installed_in_workspaces: Some(vec![]),
-            has_workspace_data: Some(false),
-            error: None,
+            app_id: "orphan-app".to_string(),
+            app_name: "Orphan App".to_string(),
+            app_path: "/path/to/app".to_string(),
+            installed_in_workspaces: vec![],
+            has_workspace_data: false,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -1531,6 +1673,125 @@ mod tests {
         assert!(json.contains("\"hasWorkspaceData\":false"));
     }
 
+    // ==================== APP PROCESS RESPONSE TESTS ====================
+
+    #[test]
+    fn test_app_process_response_from_status() {
+        let status = AppStatus {
+            running: true,
+            pid: Some(4242),
+            exit_code: None,
+            recent_output: vec!["ready on localhost:3001".to_string()],
+            actual_port: Some(3001),
+        };
+        let response = AppProcessResponse::from_status("my-app".to_string(), status);
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["success"], true);
+        assert_eq!(json["appId"], "my-app");
+        assert_eq!(json["running"], true);
+        assert_eq!(json["pid"], 4242);
+        assert_eq!(json["port"], 3001);
+        assert_eq!(json["recentOutput"][0], "ready on localhost:3001");
+        assert!(json.get("exitCode").is_none());
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    fn test_app_process_response_stopped_with_exit_code() {
+        let status = AppStatus {
+            running: false,
+            pid: None,
+            exit_code: Some(1),
+            recent_output: vec!["Error: boom".to_string()],
+            actual_port: None,
+        };
+        let json =
+            serde_json::to_value(AppProcessResponse::from_status("my-app".to_string(), status))
+                .unwrap();
+
+        assert_eq!(json["running"], false);
+        assert_eq!(json["exitCode"], 1);
+        assert!(json.get("pid").is_none());
+        assert!(json.get("port").is_none());
+    }
+
+    #[test]
+    fn test_app_logs_response_serialization() {
+        let response = AppLogsResponse {
+            success: true,
+            app_id: "my-app".to_string(),
+            lines: vec!["line 1".to_string(), "line 2".to_string()],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("\"appId\":\"my-app\""));
+        assert!(json.contains("\"lines\":[\"line 1\",\"line 2\"]"));
+        assert!(!json.contains("error"));
+    }
+
+    // ==================== CHECKPOINT REQUEST/RESPONSE TESTS ====================
+
+    #[test]
+    fn test_checkpoint_request_defaults() {
+        let json = r#"{"appId": "my-app", "conversationId": "conv-1"}"#;
+        let req: RestoreCheckpointRequest = serde_json::from_str(json).unwrap();
+        assert!(req.message_id.is_none());
+        assert!(req.snapshot_id.is_none());
+        assert!(req.paths.is_empty());
+
+        let req: CleanupCheckpointsRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.keep_last_n, 50);
+
+        let json = r#"{"appId": "my-app", "conversationId": "conv-1", "messageId": "msg-1"}"#;
+        let req: CreateCheckpointRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.message_id, "msg-1");
+        assert!(req.label.is_none());
+        assert!(req.full_hash.is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_response_flattens_result() {
+        let response = CheckpointResponse::ok(CleanupResult {
+            snapshots_deleted: 2,
+            blobs_deleted: 5,
+            bytes_freed: 1024,
+        });
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["success"], true);
+        assert_eq!(json["snapshotsDeleted"], 2);
+        assert_eq!(json["blobsDeleted"], 5);
+        assert_eq!(json["bytesFreed"], 1024);
+        assert!(json.get("result").is_none());
+        assert!(json.get("error").is_none());
+
+        let list = CheckpointResponse::ok(CheckpointList { checkpoints: vec![] });
+        let json = serde_json::to_value(&list).unwrap();
+        assert_eq!(json["checkpoints"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_response_rejects_path_ids() {
+        for id in ["../other-app", "a/b", "..", ""] {
+            let ids = vec![("app ID", "my-app".to_string()), ("conversation ID", id.to_string())];
+            let error =
+                checkpoint_response(ids, || -> Result<(), String> { panic!("should not run") })
+                    .await
+                    .unwrap_err();
+            assert_eq!(error.code, ApiErrorCode::InvalidRequest, "{}", id);
+            assert!(error.message.contains("conversation ID"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_response_maps_failures_to_internal() {
+        let ids = vec![("app ID", "my-app".to_string())];
+        let error = checkpoint_response(ids, || -> Result<(), String> { Err("boom".to_string()) })
+            .await
+            .unwrap_err();
+        assert_eq!(error, ApiError::internal("boom"));
+    }
+
     // ==================== HELPER FUNCTION TESTS ====================
 
     #[test]
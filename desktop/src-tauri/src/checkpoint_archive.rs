@@ -0,0 +1,969 @@
+//! Portable checkpoint archives
+//!
+//! Exports the checkpoint history of one app conversation as a single zip
+//! file, so it can be moved to another machine or attached to a bug report,
+//! and imports such archives back into the local checkpoint store.
+//!
+//! Archive layout:
+//! ```text
+//! {appId}-{conversationId}-{timestamp}.zip
+//! |-- export.json                 (format version, source app + conversation)
+//! |-- manifest.json
+//! |-- snapshots/{fileKey}.json    (snapshot ID, or message ID for legacy snapshots)
+//! `-- blobs/{hash}                (uncompressed file content)
+//! ```
+//!
+//! Blobs are written as plain content (the zip deflates them), so archives do
+//! not depend on the local blob codec. Git-backed snapshots are exported in
+//! blob form too, so an archive imports into any app regardless of storage.
+//!
+//! Imports are validated in full before any blob or snapshot is written: every
+//! blob must match its hash, every id and snapshot path must stay inside its
+//! directory, and every referenced blob must be in the archive or already in
+//! the local store. Snapshots already in the local manifest are skipped; the
+//! rest keep their snapshot ID unless it is taken locally.
+
+use crate::checkpoints::{
+    acquire_checkpoint_lock_at_path, ensure_blob_store_at_path, get_app_checkpoints_dir,
+    get_checkpoints_dir, hash_content, is_valid_blob_hash, load_or_create_manifest_at_path,
+    load_snapshot_at_path, new_snapshot_id, read_blob_at_path, retain_blobs_at_path,
+    save_manifest_at_path, save_snapshot_at_path, snapshot_blob_hashes, store_blob_at_path,
+    validate_id, validate_relative_path, CheckpointManifest, CheckpointSummary, Snapshot,
+    SnapshotLayout, MAX_FILE_BYTES,
+};
+use crate::checkpoint_git::GitRepo;
+use crate::paths::get_moldable_root;
+use crate::registry::safe_zip_entry_name;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Version 1 archives predate unique snapshot IDs and store every snapshot
+/// under its message ID, which is what their manifests' default layout says
+const ARCHIVE_FORMAT_VERSION: u32 = 2;
+const MIN_ARCHIVE_FORMAT_VERSION: u32 = 1;
+const ARCHIVE_INFO_ENTRY: &str = "export.json";
+const ARCHIVE_MANIFEST_ENTRY: &str = "manifest.json";
+const ARCHIVE_SNAPSHOTS_PREFIX: &str = "snapshots/";
+const ARCHIVE_BLOBS_PREFIX: &str = "blobs/";
+
+/// Upper bound for the manifest and snapshot JSON entries
+const MAX_ARCHIVE_JSON_BYTES: u64 = 16 * 1024 * 1024;
+
+// ============================================================================
+// DATA STRUCTURES
+// ============================================================================
+
+/// Contents of `export.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveInfo {
+    version: u32,
+    app_id: String,
+    conversation_id: String,
+    exported_at: DateTime<Utc>,
+}
+
+/// Result of exporting a conversation's checkpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    /// Absolute path of the written archive
+    pub path: String,
+    pub snapshot_count: usize,
+    pub blob_count: usize,
+    /// Uncompressed size of all exported blobs
+    pub total_bytes: u64,
+}
+
+/// Result of importing a checkpoint archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub app_id: String,
+    pub conversation_id: String,
+    pub snapshots_imported: usize,
+    /// Snapshots that were already in the local manifest
+    pub snapshots_skipped: usize,
+    pub blobs_created: usize,
+    pub blobs_reused: usize,
+}
+
+/// Counts gathered while writing an archive
+struct ArchiveStats {
+    snapshot_count: usize,
+    blob_count: usize,
+    total_bytes: u64,
+}
+
+/// Everything in an archive except blob content, which is only verified
+struct ArchiveContents {
+    info: ArchiveInfo,
+    manifest: CheckpointManifest,
+    /// Keyed by file key (see `CheckpointSummary::file_key`)
+    snapshots: BTreeMap<String, Snapshot>,
+    blob_hashes: BTreeSet<String>,
+}
+
+// ============================================================================
+// VALIDATION
+// ============================================================================
+
+fn validate_snapshot(snapshot: &Snapshot) -> Result<(), String> {
+    validate_id("snapshot ID", &snapshot.id)?;
+    validate_id("message ID", &snapshot.message_id)?;
+    if snapshot.git_commit.is_some() {
+        return Err(format!("Snapshot {} refers to a git commit", snapshot.id));
+    }
+
+    for entry in &snapshot.files {
+        validate_relative_path(&entry.path).map_err(|e| {
+            format!("Invalid file path in snapshot {}: {}", snapshot.message_id, e)
+        })?;
+
+        match &entry.hash {
+            Some(hash) if !is_valid_blob_hash(hash) => {
+                return Err(format!(
+                    "Invalid hash for {} in snapshot {}: {}",
+                    entry.path, snapshot.message_id, hash
+                ));
+            }
+            None if entry.exists => {
+                return Err(format!(
+                    "Missing hash for {} in snapshot {}",
+                    entry.path, snapshot.message_id
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn read_entry_limited(
+    file: &mut zip::read::ZipFile,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    if file.size() > limit {
+        return Err(format!("Archive entry too large: {}", name));
+    }
+
+    let mut content = Vec::new();
+    file.take(limit + 1)
+        .read_to_end(&mut content)
+        .map_err(|e| format!("Failed to read archive entry {}: {}", name, e))?;
+
+    // The declared size can lie; trust only what was actually inflated
+    if content.len() as u64 > limit {
+        return Err(format!("Archive entry too large: {}", name));
+    }
+
+    Ok(content)
+}
+
+/// Read and validate every entry of an archive without writing anything
+fn read_archive<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<ArchiveContents, String> {
+    let mut info: Option<ArchiveInfo> = None;
+    let mut manifest: Option<CheckpointManifest> = None;
+    let mut snapshots = BTreeMap::new();
+    let mut blob_hashes = BTreeSet::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let entry_path = safe_zip_entry_name(&file)?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = entry_path.to_string_lossy().replace('\\', "/");
+
+        if name == ARCHIVE_INFO_ENTRY {
+            let content = read_entry_limited(&mut file, &name, MAX_ARCHIVE_JSON_BYTES)?;
+            info = Some(
+                serde_json::from_slice(&content)
+                    .map_err(|e| format!("Failed to parse {}: {}", ARCHIVE_INFO_ENTRY, e))?,
+            );
+        } else if name == ARCHIVE_MANIFEST_ENTRY {
+            let content = read_entry_limited(&mut file, &name, MAX_ARCHIVE_JSON_BYTES)?;
+            manifest = Some(
+                serde_json::from_slice(&content)
+                    .map_err(|e| format!("Failed to parse manifest: {}", e))?,
+            );
+        } else if let Some(file_name) = name.strip_prefix(ARCHIVE_SNAPSHOTS_PREFIX) {
+            let file_key = file_name
+                .strip_suffix(".json")
+                .ok_or_else(|| format!("Unexpected archive entry: {}", name))?;
+            let content = read_entry_limited(&mut file, &name, MAX_ARCHIVE_JSON_BYTES)?;
+            let snapshot: Snapshot = serde_json::from_slice(&content)
+                .map_err(|e| format!("Failed to parse snapshot {}: {}", file_key, e))?;
+            validate_snapshot(&snapshot)?;
+            snapshots.insert(file_key.to_string(), snapshot);
+        } else if let Some(hash) = name.strip_prefix(ARCHIVE_BLOBS_PREFIX) {
+            if !is_valid_blob_hash(hash) {
+                return Err(format!("Invalid blob name in archive: {}", name));
+            }
+            let content = read_entry_limited(&mut file, &name, MAX_FILE_BYTES)?;
+            let actual = hash_content(&content);
+            if actual != hash {
+                return Err(format!("Blob hash mismatch: expected {}, got {}", hash, actual));
+            }
+            blob_hashes.insert(hash.to_string());
+        } else {
+            return Err(format!("Unexpected archive entry: {}", name));
+        }
+    }
+
+    let info = info.ok_or_else(|| format!("Archive is missing {}", ARCHIVE_INFO_ENTRY))?;
+    if !(MIN_ARCHIVE_FORMAT_VERSION..=ARCHIVE_FORMAT_VERSION).contains(&info.version) {
+        return Err(format!("Unsupported checkpoint archive version: {}", info.version));
+    }
+    validate_id("app ID", &info.app_id)?;
+    validate_id("conversation ID", &info.conversation_id)?;
+
+    let manifest =
+        manifest.ok_or_else(|| format!("Archive is missing {}", ARCHIVE_MANIFEST_ENTRY))?;
+    for summary in &manifest.snapshots {
+        validate_id("snapshot ID", &summary.id)?;
+        validate_id("message ID", &summary.message_id)?;
+        let snapshot = snapshots
+            .get(summary.file_key())
+            .ok_or_else(|| format!("Archive is missing snapshot: {}", summary.file_key()))?;
+        let id_matches = summary.layout == SnapshotLayout::ByMessage || snapshot.id == summary.id;
+        if !id_matches || snapshot.message_id != summary.message_id {
+            return Err(format!(
+                "Snapshot {} is stored under the wrong name: {}",
+                snapshot.id,
+                summary.file_key()
+            ));
+        }
+    }
+
+    Ok(ArchiveContents {
+        info,
+        manifest,
+        snapshots,
+        blob_hashes,
+    })
+}
+
+// ============================================================================
+// EXPORT / IMPORT
+// ============================================================================
+
+/// Write one conversation's manifest, snapshots and referenced blobs as a zip
+fn write_archive<W: Write + Seek>(
+    app_checkpoints_dir: &Path,
+    app_id: &str,
+    conversation_id: &str,
+    writer: W,
+) -> Result<ArchiveStats, String> {
+    let conversation_dir = app_checkpoints_dir.join(conversation_id);
+    if !conversation_dir.join("manifest.json").exists() {
+        return Err(format!("No checkpoints found for conversation: {}", conversation_id));
+    }
+
+    // Hold the lock so GC can't drop blobs out from under the export
+    let _lock = acquire_checkpoint_lock_at_path(app_checkpoints_dir)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id)?;
+    let store_dir = ensure_blob_store_at_path(app_checkpoints_dir)?;
+
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let write_entry = |zip: &mut zip::ZipWriter<W>, name: &str, content: &[u8]| {
+        zip.start_file(name, options)
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+        zip.write_all(content)
+            .map_err(|e| format!("Failed to write {} to archive: {}", name, e))
+    };
+
+    let info = ArchiveInfo {
+        version: ARCHIVE_FORMAT_VERSION,
+        app_id: app_id.to_string(),
+        conversation_id: conversation_id.to_string(),
+        exported_at: Utc::now(),
+    };
+    let content =
+        serde_json::to_vec_pretty(&info).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_entry(&mut zip, ARCHIVE_INFO_ENTRY, &content)?;
+
+    let content =
+        serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_entry(&mut zip, ARCHIVE_MANIFEST_ENTRY, &content)?;
+
+    let mut hashes = BTreeSet::new();
+    let mut store_hashes = BTreeSet::new();
+    let mut total_bytes = 0;
+    // Git object ID -> blob hash, for content already copied out of git
+    let mut git_blobs: BTreeMap<String, String> = BTreeMap::new();
+    for summary in &manifest.snapshots {
+        let mut snapshot = load_snapshot_at_path(&conversation_dir, summary.file_key())?;
+
+        // Git-backed content is copied out of the repository and written as blobs
+        if snapshot.git_commit.take().is_some() {
+            let repo = GitRepo::discover(Path::new(&snapshot.app_dir)).ok_or_else(|| {
+                format!(
+                    "Checkpoint {} is stored in git, but {} is not a git repository",
+                    snapshot.id, snapshot.app_dir
+                )
+            })?;
+            for entry in &mut snapshot.files {
+                let Some(oid) = entry.hash.take() else { continue };
+                let hash = match git_blobs.get(&oid) {
+                    Some(hash) => hash.clone(),
+                    None => {
+                        let content = repo.read_blob(&oid)?;
+                        let hash = hash_content(&content);
+                        if hashes.insert(hash.clone()) {
+                            total_bytes += content.len() as u64;
+                            let name = format!("{}{}", ARCHIVE_BLOBS_PREFIX, hash);
+                            write_entry(&mut zip, &name, &content)?;
+                        }
+                        git_blobs.insert(oid, hash.clone());
+                        hash
+                    }
+                };
+                entry.hash = Some(hash);
+            }
+        } else {
+            store_hashes.extend(snapshot_blob_hashes(&snapshot));
+        }
+
+        let content = serde_json::to_vec_pretty(&snapshot)
+            .map_err(|e| format!("Failed to serialize: {}", e))?;
+        let name = format!("{}{}.json", ARCHIVE_SNAPSHOTS_PREFIX, summary.file_key());
+        write_entry(&mut zip, &name, &content)?;
+    }
+
+    for hash in store_hashes {
+        if !hashes.insert(hash.clone()) {
+            continue;
+        }
+        let content = read_blob_at_path(&store_dir, &hash)?;
+        total_bytes += content.len() as u64;
+        write_entry(&mut zip, &format!("{}{}", ARCHIVE_BLOBS_PREFIX, hash), &content)?;
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+
+    Ok(ArchiveStats {
+        snapshot_count: manifest.snapshots.len(),
+        blob_count: hashes.len(),
+        total_bytes,
+    })
+}
+
+/// Validate an archive and merge it into `{checkpoints_dir}/{appId}/{conversationId}`.
+/// The app and conversation default to the ones the archive was exported from.
+fn import_archive<R: Read + Seek>(
+    reader: R,
+    checkpoints_dir: &Path,
+    app_id: Option<&str>,
+    conversation_id: Option<&str>,
+    app_dir: Option<&str>,
+) -> Result<ImportResult, String> {
+    let mut archive =
+        zip::ZipArchive::new(reader).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let contents = read_archive(&mut archive)?;
+
+    let app_id = app_id.unwrap_or(&contents.info.app_id).to_string();
+    let conversation_id = conversation_id
+        .unwrap_or(&contents.info.conversation_id)
+        .to_string();
+    validate_id("app ID", &app_id)?;
+    validate_id("conversation ID", &conversation_id)?;
+
+    let app_checkpoints_dir = checkpoints_dir.join(&app_id);
+    let conversation_dir = app_checkpoints_dir.join(&conversation_id);
+    let _lock = acquire_checkpoint_lock_at_path(&app_checkpoints_dir)?;
+    let mut manifest =
+        load_or_create_manifest_at_path(&conversation_dir, &app_id, &conversation_id)?;
+    // A snapshot is already here if its ID matches, or, for legacy snapshots
+    // that got a new ID when first imported, its capture time
+    let already_imported = |summary: &CheckpointSummary| {
+        manifest.snapshots.iter().any(|local| {
+            local.message_id == summary.message_id
+                && (local.id == summary.id || local.created_at == summary.created_at)
+        })
+    };
+    let (skipped, to_import): (Vec<_>, Vec<_>) = contents
+        .manifest
+        .snapshots
+        .iter()
+        .partition(|summary| already_imported(summary));
+
+    let store_dir = ensure_blob_store_at_path(&app_checkpoints_dir)?;
+
+    // Every blob the new snapshots need must be in the archive or already stored
+    let mut needed = BTreeSet::new();
+    for summary in &to_import {
+        needed.extend(snapshot_blob_hashes(&contents.snapshots[summary.file_key()]));
+    }
+    for hash in &needed {
+        if !contents.blob_hashes.contains(hash) && read_blob_at_path(&store_dir, hash).is_err() {
+            return Err(format!("Archive is missing blob: {}", hash));
+        }
+    }
+
+    let mut blobs_created = 0;
+    let mut blobs_reused = 0;
+    for hash in needed.iter().filter(|hash| contents.blob_hashes.contains(*hash)) {
+        let name = format!("{}{}", ARCHIVE_BLOBS_PREFIX, hash);
+        let mut file = archive
+            .by_name(&name)
+            .map_err(|e| format!("Failed to read archive entry {}: {}", name, e))?;
+        let content = read_entry_limited(&mut file, &name, MAX_FILE_BYTES)?;
+        let (stored_hash, created) = store_blob_at_path(&store_dir, &content)?;
+        if stored_hash != *hash {
+            return Err(format!("Blob hash mismatch: expected {}, got {}", hash, stored_hash));
+        }
+        if created {
+            blobs_created += 1;
+        } else {
+            blobs_reused += 1;
+        }
+    }
+
+    for summary in &to_import {
+        let mut snapshot = contents.snapshots[summary.file_key()].clone();
+        snapshot.app_id = app_id.clone();
+        snapshot.conversation_id = conversation_id.clone();
+        if let Some(app_dir) = app_dir {
+            snapshot.app_dir = app_dir.to_string();
+        }
+
+        // Legacy snapshot IDs aren't unique, so those always get a new one
+        let id_taken = manifest.snapshots.iter().any(|local| local.id == summary.id);
+        if summary.layout == SnapshotLayout::ByMessage || id_taken {
+            snapshot.id = new_snapshot_id(
+                &conversation_dir,
+                &manifest,
+                &snapshot.message_id,
+                snapshot.created_at,
+            );
+        }
+
+        save_snapshot_at_path(&conversation_dir, &snapshot)?;
+        retain_blobs_at_path(&store_dir, &snapshot_blob_hashes(&snapshot))?;
+        manifest.snapshots.push(CheckpointSummary {
+            id: snapshot.id.clone(),
+            layout: SnapshotLayout::ById,
+            ..(*summary).clone()
+        });
+    }
+
+    manifest.snapshots.sort_by_key(|summary| summary.created_at);
+    manifest.updated_at = Utc::now();
+    save_manifest_at_path(&conversation_dir, &manifest)?;
+
+    Ok(ImportResult {
+        app_id,
+        conversation_id,
+        snapshots_imported: to_import.len(),
+        snapshots_skipped: skipped.len(),
+        blobs_created,
+        blobs_reused,
+    })
+}
+
+fn default_export_path(app_id: &str, conversation_id: &str) -> Result<PathBuf, String> {
+    let exports_dir = get_moldable_root()?.join("exports");
+    Ok(exports_dir.join(format!(
+        "{}-{}-{}.zip",
+        app_id,
+        conversation_id,
+        Utc::now().format("%Y%m%d-%H%M%S")
+    )))
+}
+
+// ============================================================================
+// TAURI COMMANDS
+// ============================================================================
+
+/// Export a conversation's checkpoint history as a zip archive.
+/// Writes to `~/.moldable/exports/` unless `output_path` is given.
+#[tauri::command]
+pub fn export_checkpoints(
+    app_id: String,
+    conversation_id: String,
+    output_path: Option<String>,
+) -> Result<ExportResult, String> {
+    validate_id("app ID", &app_id)?;
+    validate_id("conversation ID", &conversation_id)?;
+
+    let output_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => default_export_path(&app_id, &conversation_id)?,
+    };
+    let parent = output_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .ok_or_else(|| "Export path has no parent directory".to_string())?;
+    fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create export directory: {}", e))?;
+
+    // Write next to the destination and move into place, so a failed export
+    // never leaves a truncated archive behind
+    let mut temp_file = tempfile::Builder::new()
+        .prefix(".checkpoints-export.tmp-")
+        .tempfile_in(parent)
+        .map_err(|e| format!("Failed to create temp archive: {}", e))?;
+
+    let app_checkpoints_dir = get_app_checkpoints_dir(&app_id)?;
+    let stats = write_archive(
+        &app_checkpoints_dir,
+        &app_id,
+        &conversation_id,
+        temp_file.as_file_mut(),
+    )?;
+
+    temp_file
+        .persist(&output_path)
+        .map_err(|e| format!("Failed to persist archive: {}", e.error))?;
+
+    info!(
+        "Exported {} checkpoints for {}/{} to {}",
+        stats.snapshot_count,
+        app_id,
+        conversation_id,
+        output_path.display()
+    );
+
+    Ok(ExportResult {
+        path: output_path.to_string_lossy().to_string(),
+        snapshot_count: stats.snapshot_count,
+        blob_count: stats.blob_count,
+        total_bytes: stats.total_bytes,
+    })
+}
+
+/// Import a checkpoint archive, merging it into the local manifest.
+/// `app_id` and `conversation_id` override the ids stored in the archive;
+/// `app_dir` rewrites where imported snapshots restore to.
+#[tauri::command]
+pub fn import_checkpoints(
+    archive_path: String,
+    app_id: Option<String>,
+    conversation_id: Option<String>,
+    app_dir: Option<String>,
+) -> Result<ImportResult, String> {
+    let file =
+        fs::File::open(&archive_path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let checkpoints_dir = get_checkpoints_dir()?;
+
+    let result = import_archive(
+        file,
+        &checkpoints_dir,
+        app_id.as_deref(),
+        conversation_id.as_deref(),
+        app_dir.as_deref(),
+    )?;
+
+    info!(
+        "Imported {} checkpoints into {}/{} ({} already present)",
+        result.snapshots_imported,
+        result.app_id,
+        result.conversation_id,
+        result.snapshots_skipped
+    );
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoints::{FileEntry, SnapshotKind};
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    /// Store a conversation with one snapshot per `(message_id, files)` pair
+    fn seed_conversation(
+        checkpoints_dir: &Path,
+        app_id: &str,
+        conversation_id: &str,
+        snapshots: &[(&str, &[(&str, &str)])],
+    ) {
+        let app_checkpoints_dir = checkpoints_dir.join(app_id);
+        let conversation_dir = app_checkpoints_dir.join(conversation_id);
+        let store_dir = ensure_blob_store_at_path(&app_checkpoints_dir).unwrap();
+        let mut manifest =
+            load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id).unwrap();
+
+        for (offset, (message_id, files)) in snapshots.iter().enumerate() {
+            let created_at = Utc::now() + chrono::Duration::seconds(offset as i64);
+            let mut entries = Vec::new();
+            for (path, content) in files.iter() {
+                let (hash, _) = store_blob_at_path(&store_dir, content.as_bytes()).unwrap();
+                entries.push(FileEntry {
+                    path: path.to_string(),
+                    hash: Some(hash),
+                    size: content.len() as u64,
+                    mode: 0o644,
+                    exists: true,
+                    binary: false,
+                });
+            }
+            let snapshot = Snapshot {
+                id: format!("snap-{}", message_id),
+                message_id: message_id.to_string(),
+                conversation_id: conversation_id.to_string(),
+                app_id: app_id.to_string(),
+                app_dir: "/original/app".to_string(),
+                created_at,
+                files: entries,
+                kind: SnapshotKind::Message,
+                restored_message_id: None,
+                restored_snapshot_id: None,
+                label: None,
+                git_commit: None,
+            };
+            save_snapshot_at_path(&conversation_dir, &snapshot).unwrap();
+            retain_blobs_at_path(&store_dir, &snapshot_blob_hashes(&snapshot)).unwrap();
+            manifest.snapshots.push(CheckpointSummary {
+                id: snapshot.id.clone(),
+                message_id: message_id.to_string(),
+                created_at,
+                file_count: files.len(),
+                total_bytes: 0,
+                has_changes: false,
+                kind: SnapshotKind::Message,
+                label: None,
+                layout: SnapshotLayout::ById,
+            });
+        }
+
+        save_manifest_at_path(&conversation_dir, &manifest).unwrap();
+    }
+
+    fn export_bytes(checkpoints_dir: &Path, app_id: &str, conversation_id: &str) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_archive(&checkpoints_dir.join(app_id), app_id, conversation_id, &mut cursor)
+            .unwrap();
+        cursor.into_inner()
+    }
+
+    fn archive_entries(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).unwrap();
+                (file.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    fn build_archive(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut zip = zip::ZipWriter::new(&mut cursor);
+        for (name, content) in entries {
+            zip.start_file(name.as_str(), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+        cursor.into_inner()
+    }
+
+    fn import_bytes(bytes: Vec<u8>, checkpoints_dir: &Path) -> Result<ImportResult, String> {
+        import_archive(Cursor::new(bytes), checkpoints_dir, None, None, None)
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(
+            source.path(),
+            "notes",
+            "conv-1",
+            &[
+                ("msg-1", &[("src/a.ts", "one"), ("README.md", "readme")]),
+                ("msg-2", &[("src/a.ts", "two"), ("README.md", "readme")]),
+            ],
+        );
+        let bytes = export_bytes(source.path(), "notes", "conv-1");
+
+        let target = TempDir::new().unwrap();
+        let result = import_archive(
+            Cursor::new(bytes),
+            target.path(),
+            None,
+            None,
+            Some("/new/app"),
+        )
+        .unwrap();
+
+        assert_eq!(result.app_id, "notes");
+        assert_eq!(result.conversation_id, "conv-1");
+        assert_eq!(result.snapshots_imported, 2);
+        assert_eq!(result.snapshots_skipped, 0);
+        assert_eq!(result.blobs_created, 3);
+
+        let conversation_dir = target.path().join("notes").join("conv-1");
+        let manifest =
+            load_or_create_manifest_at_path(&conversation_dir, "notes", "conv-1").unwrap();
+        let message_ids: Vec<_> = manifest.snapshots.iter().map(|s| &s.message_id).collect();
+        assert_eq!(message_ids, ["msg-1", "msg-2"]);
+
+        let snapshot = load_snapshot_at_path(&conversation_dir, "snap-msg-2").unwrap();
+        assert_eq!(snapshot.app_dir, "/new/app");
+        let store_dir = ensure_blob_store_at_path(&target.path().join("notes")).unwrap();
+        let entry = snapshot.files.iter().find(|f| f.path == "src/a.ts").unwrap();
+        let content = read_blob_at_path(&store_dir, entry.hash.as_ref().unwrap()).unwrap();
+        assert_eq!(content, b"two");
+    }
+
+    #[test]
+    fn test_export_writes_git_snapshots_as_blobs() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(source.path(), "notes", "conv-1", &[("msg-1", &[("a.ts", "one")])]);
+
+        // Move the snapshot's content into a git repository
+        let app_dir = source.path().join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        let status = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .arg(&app_dir)
+            .env_remove("GIT_DIR")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::write(app_dir.join("a.ts"), "one").unwrap();
+        let repo = GitRepo::discover(&app_dir).unwrap();
+
+        let conversation_dir = source.path().join("notes").join("conv-1");
+        let mut snapshot = load_snapshot_at_path(&conversation_dir, "snap-msg-1").unwrap();
+        let blob_hash = snapshot.files[0].hash.clone().unwrap();
+        snapshot.app_dir = app_dir.to_string_lossy().to_string();
+        repo.hash_files(&mut snapshot.files, true).unwrap();
+        snapshot.git_commit = Some(repo.commit_snapshot(&snapshot).unwrap());
+        save_snapshot_at_path(&conversation_dir, &snapshot).unwrap();
+
+        let bytes = export_bytes(source.path(), "notes", "conv-1");
+        let entries = archive_entries(&bytes);
+        let (_, content) = entries
+            .iter()
+            .find(|(name, _)| name == "snapshots/snap-msg-1.json")
+            .unwrap();
+        let exported: Snapshot = serde_json::from_slice(content).unwrap();
+        assert!(exported.git_commit.is_none());
+        assert_eq!(exported.files[0].hash.as_deref(), Some(blob_hash.as_str()));
+        assert!(entries
+            .iter()
+            .any(|(name, content)| *name == format!("blobs/{}", blob_hash) && content == b"one"));
+
+        let target = TempDir::new().unwrap();
+        let result = import_bytes(bytes, target.path()).unwrap();
+        assert_eq!(result.snapshots_imported, 1);
+    }
+
+    #[test]
+    fn test_import_merges_and_skips_existing_snapshots() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(
+            source.path(),
+            "notes",
+            "conv-1",
+            &[("msg-1", &[("a.txt", "one")]), ("msg-2", &[("a.txt", "two")])],
+        );
+        let bytes = export_bytes(source.path(), "notes", "conv-1");
+
+        let target = TempDir::new().unwrap();
+        seed_conversation(target.path(), "notes", "conv-1", &[("msg-1", &[("a.txt", "one")])]);
+
+        let result = import_bytes(bytes.clone(), target.path()).unwrap();
+        assert_eq!(result.snapshots_imported, 1);
+        assert_eq!(result.snapshots_skipped, 1);
+        assert_eq!(result.blobs_created, 1);
+
+        // Importing the same archive again changes nothing
+        let again = import_bytes(bytes, target.path()).unwrap();
+        assert_eq!(again.snapshots_imported, 0);
+        assert_eq!(again.snapshots_skipped, 2);
+        assert_eq!(again.blobs_created + again.blobs_reused, 0);
+
+        let conversation_dir = target.path().join("notes").join("conv-1");
+        let manifest =
+            load_or_create_manifest_at_path(&conversation_dir, "notes", "conv-1").unwrap();
+        assert_eq!(manifest.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn test_import_legacy_archive_assigns_snapshot_ids() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(
+            source.path(),
+            "notes",
+            "conv-1",
+            &[("msg-1", &[("a.txt", "one")]), ("msg-2", &[("a.txt", "two")])],
+        );
+
+        // Rewrite the export the way version 1 wrote it: colliding snapshot IDs,
+        // snapshots stored by message ID, and no layout in the manifest
+        let mut entries = Vec::new();
+        for (name, content) in archive_entries(&export_bytes(source.path(), "notes", "conv-1")) {
+            let mut json: serde_json::Value = match serde_json::from_slice(&content) {
+                Ok(json) => json,
+                Err(_) => {
+                    entries.push((name, content));
+                    continue;
+                }
+            };
+            let name = if name == ARCHIVE_INFO_ENTRY {
+                json["version"] = 1.into();
+                name
+            } else if name == ARCHIVE_MANIFEST_ENTRY {
+                for summary in json["snapshots"].as_array_mut().unwrap() {
+                    summary["id"] = "snap-msg-".into();
+                    summary.as_object_mut().unwrap().remove("layout");
+                }
+                name
+            } else {
+                json["id"] = "snap-msg-".into();
+                format!("{}{}.json", ARCHIVE_SNAPSHOTS_PREFIX, json["messageId"].as_str().unwrap())
+            };
+            entries.push((name, serde_json::to_vec(&json).unwrap()));
+        }
+        let bytes = build_archive(&entries);
+
+        let target = TempDir::new().unwrap();
+        let result = import_bytes(bytes.clone(), target.path()).unwrap();
+        assert_eq!(result.snapshots_imported, 2);
+
+        let conversation_dir = target.path().join("notes").join("conv-1");
+        let manifest =
+            load_or_create_manifest_at_path(&conversation_dir, "notes", "conv-1").unwrap();
+        assert_ne!(manifest.snapshots[0].id, manifest.snapshots[1].id);
+        for summary in &manifest.snapshots {
+            assert_eq!(summary.layout, SnapshotLayout::ById);
+            let snapshot = load_snapshot_at_path(&conversation_dir, &summary.id).unwrap();
+            assert_eq!(snapshot.id, summary.id);
+            assert_eq!(snapshot.message_id, summary.message_id);
+        }
+
+        // The new IDs don't make a second import look like new snapshots
+        let again = import_bytes(bytes, target.path()).unwrap();
+        assert_eq!(again.snapshots_imported, 0);
+        assert_eq!(again.snapshots_skipped, 2);
+    }
+
+    #[test]
+    fn test_import_into_other_conversation() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(source.path(), "notes", "conv-1", &[("msg-1", &[("a.txt", "one")])]);
+        let bytes = export_bytes(source.path(), "notes", "conv-1");
+
+        let target = TempDir::new().unwrap();
+        let result =
+            import_archive(Cursor::new(bytes), target.path(), Some("todo"), Some("conv-9"), None)
+                .unwrap();
+        assert_eq!(result.snapshots_imported, 1);
+
+        let snapshot =
+            load_snapshot_at_path(&target.path().join("todo").join("conv-9"), "snap-msg-1")
+                .unwrap();
+        assert_eq!(snapshot.app_id, "todo");
+        assert_eq!(snapshot.conversation_id, "conv-9");
+    }
+
+    #[test]
+    fn test_import_rejects_tampered_blob() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(source.path(), "notes", "conv-1", &[("msg-1", &[("a.txt", "one")])]);
+        let mut entries = archive_entries(&export_bytes(source.path(), "notes", "conv-1"));
+        for (name, content) in entries.iter_mut() {
+            if name.starts_with(ARCHIVE_BLOBS_PREFIX) {
+                *content = b"evil".to_vec();
+            }
+        }
+
+        let target = TempDir::new().unwrap();
+        let err = import_bytes(build_archive(&entries), target.path()).unwrap_err();
+        assert!(err.contains("hash mismatch"), "{}", err);
+        assert!(!target.path().join("notes").exists());
+    }
+
+    #[test]
+    fn test_import_rejects_missing_blob() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(source.path(), "notes", "conv-1", &[("msg-1", &[("a.txt", "one")])]);
+        let entries: Vec<_> = archive_entries(&export_bytes(source.path(), "notes", "conv-1"))
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with(ARCHIVE_BLOBS_PREFIX))
+            .collect();
+
+        let target = TempDir::new().unwrap();
+        let err = import_bytes(build_archive(&entries), target.path()).unwrap_err();
+        assert!(err.contains("missing blob"), "{}", err);
+        assert!(!target.path().join("notes").join("conv-1").exists());
+    }
+
+    #[test]
+    fn test_import_rejects_path_traversal() {
+        let source = TempDir::new().unwrap();
+        seed_conversation(source.path(), "notes", "conv-1", &[("msg-1", &[("a.txt", "one")])]);
+        let entries = archive_entries(&export_bytes(source.path(), "notes", "conv-1"));
+        let target = TempDir::new().unwrap();
+
+        // A snapshot that would restore outside the app directory
+        let escaping: Vec<_> = entries
+            .iter()
+            .map(|(name, content)| {
+                let content = if name.starts_with(ARCHIVE_SNAPSHOTS_PREFIX) {
+                    String::from_utf8(content.clone())
+                        .unwrap()
+                        .replace("\"a.txt\"", "\"../../escape.txt\"")
+                        .into_bytes()
+                } else {
+                    content.clone()
+                };
+                (name.clone(), content)
+            })
+            .collect();
+        let err = import_bytes(build_archive(&escaping), target.path()).unwrap_err();
+        assert!(err.contains("invalid component"), "{}", err);
+
+        // An archive entry that would be written outside the archive root
+        let mut zip_slip = entries.clone();
+        zip_slip.push(("../evil.txt".to_string(), b"evil".to_vec()));
+        let err = import_bytes(build_archive(&zip_slip), target.path()).unwrap_err();
+        assert!(err.contains("Unsafe zip entry path"), "{}", err);
+
+        // Ids are used as directory names
+        let err = import_archive(
+            Cursor::new(build_archive(&entries)),
+            target.path(),
+            Some("../other"),
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.contains("Invalid app ID"), "{}", err);
+
+        assert!(fs::read_dir(target.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_validate_id() {
+        assert!(validate_id("message ID", "msg-123").is_ok());
+        assert!(validate_id("message ID", "").is_err());
+        assert!(validate_id("message ID", "..").is_err());
+        assert!(validate_id("message ID", ".").is_err());
+        assert!(validate_id("message ID", "a/b").is_err());
+        assert!(validate_id("message ID", "a\\b").is_err());
+        assert!(validate_id("message ID", "/abs").is_err());
+    }
+}
@@ -0,0 +1,411 @@
+//! Git-backed checkpoint storage
+//!
+//! Apps whose moldable.json sets `"checkpoint": { "storage": "git" }` and that
+//! live in a git repository keep checkpoint content in that repository instead
+//! of the blob store. Each checkpoint is a commit of the captured files on
+//! `refs/moldable/checkpoints/{conversationId}`, whose parent is the
+//! conversation's previous checkpoint, so `git log` and `git diff` work on
+//! them. The commits are built from a temporary index: the user's branches,
+//! index and HEAD are never touched.
+//!
+//! The app directory is the root of every checkpoint tree, also when the app
+//! is a subdirectory of a larger repository.
+//!
+//! Snapshot files and the conversation manifest are still written as usual, so
+//! snapshot IDs, labels and retention work the same in both modes. A git-backed
+//! snapshot records its commit and uses git object IDs as file hashes.
+//! Deleting a snapshot leaves its commit in the ref's history; removing the
+//! ref (`git update-ref -d`) lets `git gc` reclaim them.
+
+use crate::checkpoints::{FileEntry, Snapshot, SnapshotKind};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Namespace for checkpoint refs; not a branch or tag, so git tooling hides it
+const CHECKPOINT_REF_PREFIX: &str = "refs/moldable/checkpoints/";
+
+/// Author and committer of checkpoint commits
+const CHECKPOINT_AUTHOR_NAME: &str = "Moldable";
+const CHECKPOINT_AUTHOR_EMAIL: &str = "checkpoints@moldable.sh";
+
+/// Whether a string is a full SHA-1 or SHA-256 git object ID
+pub(crate) fn is_valid_git_oid(oid: &str) -> bool {
+    matches!(oid.len(), 40 | 64)
+        && oid
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+/// Git file mode for a captured file
+fn git_mode(mode: u32) -> &'static str {
+    if mode & 0o111 != 0 {
+        "100755"
+    } else {
+        "100644"
+    }
+}
+
+/// An app directory inside a git repository, used as the work tree root
+#[derive(Debug, Clone)]
+pub(crate) struct GitRepo {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+impl GitRepo {
+    /// The repository containing `app_dir`, or `None` if there is none or git
+    /// isn't installed
+    pub(crate) fn discover(app_dir: &Path) -> Option<Self> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(app_dir)
+            .args(["rev-parse", "--absolute-git-dir"])
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let git_dir = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        Some(Self {
+            git_dir: PathBuf::from(git_dir),
+            work_tree: app_dir.to_path_buf(),
+        })
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new("git");
+        command
+            .current_dir(&self.work_tree)
+            .env("GIT_DIR", &self.git_dir)
+            .env("GIT_WORK_TREE", &self.work_tree)
+            .env("GIT_AUTHOR_NAME", CHECKPOINT_AUTHOR_NAME)
+            .env("GIT_AUTHOR_EMAIL", CHECKPOINT_AUTHOR_EMAIL)
+            .env("GIT_COMMITTER_NAME", CHECKPOINT_AUTHOR_NAME)
+            .env("GIT_COMMITTER_EMAIL", CHECKPOINT_AUTHOR_EMAIL)
+            .env_remove("GIT_INDEX_FILE");
+        command
+    }
+
+    /// Run git with `args`, feeding `input` on stdin, and return its stdout.
+    /// `index_file` replaces the repository's index for this command.
+    fn run(
+        &self,
+        args: &[&str],
+        input: Option<Vec<u8>>,
+        index_file: Option<&Path>,
+    ) -> Result<Vec<u8>, String> {
+        let mut command = self.command();
+        command
+            .args(args)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(index_file) = index_file {
+            command.env("GIT_INDEX_FILE", index_file);
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        // Write stdin from another thread so a full stdout pipe can't deadlock
+        let writer = match (input, child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => {
+                Some(std::thread::spawn(move || stdin.write_all(&input)))
+            }
+            _ => None,
+        };
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        if let Some(writer) = writer {
+            writer
+                .join()
+                .map_err(|_| "Failed to write to git".to_string())?
+                .map_err(|e| format!("Failed to write to git: {}", e))?;
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn run_for_line(&self, args: &[&str], index_file: Option<&Path>) -> Result<String, String> {
+        let stdout = self.run(args, None, index_file)?;
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+
+    /// The ref holding a conversation's checkpoints, or `None` if the
+    /// conversation ID can't be part of a ref name
+    pub(crate) fn checkpoint_ref(&self, conversation_id: &str) -> Option<String> {
+        if conversation_id.contains('/') {
+            return None;
+        }
+        let name = format!("{}{}", CHECKPOINT_REF_PREFIX, conversation_id);
+        self.run(&["check-ref-format", &name], None, None)
+            .ok()
+            .map(|_| name)
+    }
+
+    /// Replace the hash of every existing file in `files` with its git object
+    /// ID. With `write`, the content is also added to the repository.
+    pub(crate) fn hash_files(&self, files: &mut [FileEntry], write: bool) -> Result<(), String> {
+        let mut input = String::new();
+        for file in files.iter().filter(|f| f.exists) {
+            if file.path.contains(['\n', '\r']) {
+                return Err(format!("Can't store file name in git: {:?}", file.path));
+            }
+            input.push_str(&file.path);
+            input.push('\n');
+        }
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        // No filters: the object holds the bytes on disk, so restores are exact
+        let mut args = vec!["hash-object", "--no-filters", "--stdin-paths"];
+        if write {
+            args.push("-w");
+        }
+        let stdout = self.run(&args, Some(input.into_bytes()), None)?;
+        let stdout = String::from_utf8_lossy(&stdout);
+        let mut oids = stdout.lines();
+
+        for file in files.iter_mut().filter(|f| f.exists) {
+            let oid = oids
+                .next()
+                .filter(|oid| is_valid_git_oid(oid))
+                .ok_or_else(|| format!("git hash-object gave no object ID for {}", file.path))?;
+            file.hash = Some(oid.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Commit the files of `snapshot` (already hashed with `hash_files`) on its
+    /// conversation's checkpoint ref, and return the commit ID
+    pub(crate) fn commit_snapshot(&self, snapshot: &Snapshot) -> Result<String, String> {
+        let checkpoint_ref = self
+            .checkpoint_ref(&snapshot.conversation_id)
+            .ok_or_else(|| {
+                format!("Invalid conversation ID for git: {}", snapshot.conversation_id)
+            })?;
+
+        let mut index_info = String::new();
+        for file in snapshot.files.iter().filter(|f| f.exists) {
+            let oid = file
+                .hash
+                .as_deref()
+                .filter(|oid| is_valid_git_oid(oid))
+                .ok_or_else(|| format!("Missing git object ID for file: {}", file.path))?;
+            let path = file.path.replace('\\', "/");
+            index_info.push_str(&format!("{} {}\t{}\0", git_mode(file.mode), oid, path));
+        }
+
+        let index_dir = tempfile::Builder::new()
+            .prefix("moldable-checkpoint-index")
+            .tempdir()
+            .map_err(|e| format!("Failed to create temp index: {}", e))?;
+        let index_file = index_dir.path().join("index");
+        self.run(
+            &["update-index", "-z", "--index-info"],
+            Some(index_info.into_bytes()),
+            Some(&index_file),
+        )?;
+        let tree = self.run_for_line(&["write-tree"], Some(&index_file))?;
+
+        let parent_spec = format!("{}^{{commit}}", checkpoint_ref);
+        let parent = self
+            .run_for_line(&["rev-parse", "--verify", "-q", &parent_spec], None)
+            .ok()
+            .filter(|oid| is_valid_git_oid(oid));
+
+        let mut args = vec!["commit-tree", "--no-gpg-sign", tree.as_str()];
+        if let Some(parent) = &parent {
+            args.extend(["-p", parent.as_str()]);
+        }
+        args.extend(["-F", "-"]);
+        let commit = self.run(&args, Some(commit_message(snapshot).into_bytes()), None)?;
+        let commit = String::from_utf8_lossy(&commit).trim().to_string();
+        if !is_valid_git_oid(&commit) {
+            return Err(format!("git commit-tree gave an invalid commit ID: {}", commit));
+        }
+
+        // Fails if another writer moved the ref since we read the parent
+        let expected = parent.as_deref().unwrap_or("");
+        self.run(
+            &["update-ref", "-m", "moldable: checkpoint", &checkpoint_ref, &commit, expected],
+            None,
+            None,
+        )?;
+
+        Ok(commit)
+    }
+
+    /// Content of a blob, by object ID
+    pub(crate) fn read_blob(&self, oid: &str) -> Result<Vec<u8>, String> {
+        if !is_valid_git_oid(oid) {
+            return Err(format!("Invalid git object ID: {}", oid));
+        }
+        self.run(&["cat-file", "blob", oid], None, None)
+            .map_err(|e| format!("Failed to read {} from git: {}", oid, e))
+    }
+
+    /// Whether the repository has the given commit
+    pub(crate) fn has_commit(&self, oid: &str) -> bool {
+        is_valid_git_oid(oid)
+            && self
+                .run(&["cat-file", "-e", &format!("{}^{{commit}}", oid)], None, None)
+                .is_ok()
+    }
+}
+
+/// Commit message for a checkpoint: a summary line plus trailers to find a
+/// commit's snapshot from `git log`
+fn commit_message(snapshot: &Snapshot) -> String {
+    let summary = match snapshot.kind {
+        SnapshotKind::Message => format!("Checkpoint before message {}", snapshot.message_id),
+        SnapshotKind::PreRestore => "Working tree before restoring a checkpoint".to_string(),
+    };
+
+    let mut message = format!(
+        "{}\n\nMoldable-Snapshot: {}\nMoldable-Message: {}\n",
+        summary, snapshot.id, snapshot.message_id
+    );
+    if let Some(label) = &snapshot.label {
+        // Trailers are single lines
+        message.push_str(&format!("Moldable-Label: {}\n", label.replace(['\n', '\r'], " ")));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn init_repo(dir: &Path) -> GitRepo {
+        let status = Command::new("git")
+            .args(["init", "-q"])
+            .arg(dir)
+            .env_remove("GIT_DIR")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        GitRepo::discover(dir).unwrap()
+    }
+
+    fn snapshot_of(repo: &GitRepo, files: &[(&str, &str)]) -> Snapshot {
+        let mut entries: Vec<FileEntry> = files
+            .iter()
+            .map(|(path, content)| {
+                fs::write(repo.work_tree.join(path), content).unwrap();
+                FileEntry {
+                    path: path.to_string(),
+                    hash: None,
+                    size: content.len() as u64,
+                    mode: 0o644,
+                    exists: true,
+                    binary: false,
+                }
+            })
+            .collect();
+        repo.hash_files(&mut entries, true).unwrap();
+
+        Snapshot {
+            id: format!("snap-{}", files.len()),
+            message_id: "msg".to_string(),
+            conversation_id: "conv-1".to_string(),
+            app_id: "app".to_string(),
+            app_dir: repo.work_tree.to_string_lossy().to_string(),
+            created_at: Utc::now(),
+            files: entries,
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: Some("tool call".to_string()),
+            git_commit: None,
+        }
+    }
+
+    #[test]
+    fn test_discover_outside_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(GitRepo::discover(temp_dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_hash_and_read_blob_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_repo(temp_dir.path());
+        let snapshot = snapshot_of(&repo, &[("a.ts", "alpha\r\n"), ("b.ts", "beta")]);
+
+        for (file, expected) in snapshot.files.iter().zip(["alpha\r\n", "beta"]) {
+            let oid = file.hash.as_deref().unwrap();
+            assert!(is_valid_git_oid(oid));
+            assert_eq!(repo.read_blob(oid).unwrap(), expected.as_bytes());
+        }
+        assert!(repo.read_blob("--batch").is_err());
+    }
+
+    #[test]
+    fn test_commit_snapshot_chains_on_hidden_ref() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_repo(temp_dir.path());
+
+        let first = repo.commit_snapshot(&snapshot_of(&repo, &[("a.ts", "v1")])).unwrap();
+        let second = repo
+            .commit_snapshot(&snapshot_of(&repo, &[("a.ts", "v2"), ("b.ts", "new")]))
+            .unwrap();
+        assert!(repo.has_commit(&first));
+        assert!(repo.has_commit(&second));
+
+        let head = repo
+            .run_for_line(&["rev-parse", "refs/moldable/checkpoints/conv-1"], None)
+            .unwrap();
+        assert_eq!(head, second);
+        let parent = repo
+            .run_for_line(&["rev-parse", &format!("{}^", second)], None)
+            .unwrap();
+        assert_eq!(parent, first);
+
+        let files = repo
+            .run_for_line(&["ls-tree", "--name-only", &second], None)
+            .unwrap();
+        assert_eq!(files, "a.ts\nb.ts");
+        let message = repo.run_for_line(&["log", "-1", "--format=%B", &second], None).unwrap();
+        assert!(message.contains("Moldable-Label: tool call"));
+
+        // Branches and the index are left alone
+        assert!(repo.run_for_line(&["rev-parse", "--verify", "HEAD"], None).is_err());
+        let status = repo.run_for_line(&["status", "--porcelain"], None).unwrap();
+        assert!(status.contains("?? a.ts"));
+    }
+
+    #[test]
+    fn test_checkpoint_ref_rejects_invalid_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_repo(temp_dir.path());
+
+        assert_eq!(
+            repo.checkpoint_ref("conv-1").as_deref(),
+            Some("refs/moldable/checkpoints/conv-1")
+        );
+        assert!(repo.checkpoint_ref("a/b").is_none());
+        assert!(repo.checkpoint_ref("bad..name").is_none());
+        assert!(repo.checkpoint_ref("has space").is_none());
+    }
+}
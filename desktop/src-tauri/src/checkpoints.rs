@@ -7,19 +7,54 @@
 //! ```text
 //! ~/.moldable/workspaces/{workspaceId}/checkpoints/
 //! `-- {appId}/
+//!     |-- .stat-cache.json           (path, size, mtime, inode -> hash)
+//!     |-- .store/
+//!     |   |-- refs.json              (hash -> number of snapshots using it)
+//!     |   `-- blobs/{hash-prefix}/{hash}
 //!     `-- {conversationId}/
 //!         |-- manifest.json
-//!         |-- blobs/{hash-prefix}/{hash}
 //!         `-- snapshots/{messageId}.json
 //! ```
+//!
+//! Blobs are shared by every conversation of an app, so unchanged files are
+//! stored once. A blob is deleted only when no snapshot references it anymore.
+//!
+//! Blobs are named by the SHA-256 of their uncompressed content and stored
+//! zstd-compressed behind a small codec header. Blobs written before the
+//! header existed are raw content and are still read as-is.
+//!
+//! Working-tree hashes are cached by file stat, so unchanged files are not
+//! re-read when checkpointing or listing.
+//!
+//! Which files are captured is decided by built-in defaults (dependency and
+//! build directories, lock files), then the app's root `.gitignore`, then
+//! `.moldableignore`, then the `checkpoint` section of `moldable.json`.
+//! All use gitignore syntax and the last matching rule wins, so `!pattern`
+//! in `.moldableignore` or a `checkpoint.include` entry can bring back files
+//! the defaults skip (e.g. `*.lock`).
+//!
+//! Binary files (images, fonts, databases, ...) are captured like any other
+//! file as long as they are under `checkpoint.maxBinaryBytes` (1MB default).
+//!
+//! With `checkpoint.storage` set to `"git"`, apps in a git repository keep
+//! checkpoint content in that repository instead of the blob store (see
+//! `checkpoint_git`). Manifests and snapshot files are written either way.
 
+use crate::checkpoint_git::GitRepo;
 use crate::paths::get_active_workspace_dir;
+use crate::preferences::load_shared_config;
+use crate::types::{CheckpointConfig, CheckpointStorage, MoldableManifest};
 use chrono::{DateTime, Utc};
+use log::{info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
-use std::fs;
+use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 // ============================================================================
@@ -49,7 +84,7 @@ const IGNORED_DIR_NAMES: &[&str] = &[
     ".nyc_output",
 ];
 
-/// Binary file extensions to skip
+/// Extensions always treated as binary (other files are sniffed for NUL bytes)
 const BINARY_EXTENSIONS: &[&str] = &[
     // Images
     "png", "jpg", "jpeg", "gif", "webp", "ico", "svg", "bmp", "tiff",
@@ -63,41 +98,229 @@ const BINARY_EXTENSIONS: &[&str] = &[
     "exe", "dll", "so", "dylib", "bin",
     // Other
     "pdf", "db", "sqlite", "sqlite3",
+];
+
+/// File extensions skipped by default
+const DEFAULT_IGNORED_EXTENSIONS: &[&str] = &[
     // Lock files (large, not useful to checkpoint)
     "lock",
 ];
 
+/// Default max size of a binary file to checkpoint (1MB)
+const DEFAULT_MAX_BINARY_BYTES: u64 = 1024 * 1024;
+/// How much of a file to inspect when sniffing for binary content (same as git)
+const BINARY_SNIFF_BYTES: usize = 8000;
+
 /// Max bytes per file (10MB) and total bytes per checkpoint (100MB)
-const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+pub(crate) const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
 const MAX_CHECKPOINT_BYTES: u64 = 100 * 1024 * 1024;
 
+/// Blob store directory inside an app's checkpoint directory
+const BLOB_STORE_DIR: &str = ".store";
+/// Reference count file inside the blob store
+const BLOB_REFS_FILE: &str = "refs.json";
+
+/// Magic prefix of a blob written with a codec header
+const BLOB_MAGIC: &[u8; 4] = b"MLDB";
+/// Codec byte for content stored as-is (compression didn't help)
+const BLOB_CODEC_RAW: u8 = 0;
+/// Codec byte for zstd-compressed content
+const BLOB_CODEC_ZSTD: u8 = 1;
+/// zstd compression level (the library default)
+const BLOB_ZSTD_LEVEL: i32 = 3;
+
+/// Stat cache file inside an app's checkpoint directory
+const STAT_CACHE_FILE: &str = ".stat-cache.json";
+/// Files modified this recently before a scan aren't cached, since a further
+/// write within the filesystem's timestamp granularity would go unnoticed
+const STAT_CACHE_RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Lock file inside an app's checkpoint directory
+const CHECKPOINT_LOCK_FILE: &str = ".lock";
+/// Captures hold the lock while hashing the working tree, so waiters get
+/// longer than the 2 seconds other config locks allow
+const CHECKPOINT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+const CHECKPOINT_LOCK_STALE_AFTER: Duration = Duration::from_secs(120);
+
 /// Check if a directory should be ignored
 fn should_ignore_dir(name: &str) -> bool {
     IGNORED_DIR_NAMES.contains(&name)
 }
 
+/// Lowercased extension of a path, if any
+fn extension_of(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_lowercase())
+}
+
 /// Check if a file is binary based on extension
 fn is_binary_file(path: &Path) -> bool {
-    if let Some(ext) = path.extension() {
-        if let Some(ext_str) = ext.to_str() {
-            return BINARY_EXTENSIONS.contains(&ext_str.to_lowercase().as_str());
+    extension_of(path).is_some_and(|ext| BINARY_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Check if a file is skipped by default based on extension
+fn is_default_ignored_file(path: &Path) -> bool {
+    extension_of(path).is_some_and(|ext| DEFAULT_IGNORED_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Check if content looks binary (contains a NUL byte near the start)
+fn looks_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+/// A single gitignore-style pattern
+#[derive(Debug)]
+struct IgnoreRule {
+    pattern: glob::Pattern,
+    /// `!pattern`: re-include matching paths
+    negated: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    /// Parse one line of a gitignore-style file. Returns None for blank lines,
+    /// comments, and patterns that aren't valid globs.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
         }
+
+        let (negated, body) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let dir_only = body.ends_with('/');
+        let body = body.trim_end_matches('/');
+
+        // A slash anywhere but the end anchors the pattern to the app root;
+        // otherwise it matches at any depth
+        let anchored = body.contains('/');
+        let body = body.trim_start_matches('/');
+        if body.is_empty() {
+            return None;
+        }
+
+        let glob = if anchored {
+            body.to_string()
+        } else {
+            format!("**/{}", body)
+        };
+
+        Some(Self {
+            pattern: glob::Pattern::new(&glob).ok()?,
+            negated,
+            dir_only,
+        })
     }
-    false
+
+    fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.pattern.matches_with(relative_path, options)
+    }
+}
+
+/// Decides which paths in an app directory are captured by checkpoints
+#[derive(Debug)]
+struct ScanRules {
+    /// Rules in precedence order; the last one that matches a path wins
+    rules: Vec<IgnoreRule>,
+    /// Binary files larger than this are skipped
+    max_binary_bytes: u64,
+}
+
+impl Default for ScanRules {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            max_binary_bytes: DEFAULT_MAX_BINARY_BYTES,
+        }
+    }
+}
+
+impl ScanRules {
+    /// Load rules from the app's `.gitignore`, `.moldableignore` and `moldable.json`
+    fn for_app_dir(app_dir: &Path) -> Self {
+        let read = |name: &str| fs::read_to_string(app_dir.join(name)).unwrap_or_default();
+        let config = checkpoint_config(app_dir);
+        Self::from_sources(&read(".gitignore"), &read(".moldableignore"), &config)
+    }
+
+    fn from_sources(gitignore: &str, moldableignore: &str, config: &CheckpointConfig) -> Self {
+        let mut rules: Vec<IgnoreRule> = gitignore
+            .lines()
+            .chain(moldableignore.lines())
+            .filter_map(IgnoreRule::parse)
+            .collect();
+
+        let config_rules = |patterns: &[String], negated: bool| -> Vec<IgnoreRule> {
+            patterns
+                .iter()
+                .filter_map(|p| IgnoreRule::parse(p.trim_start_matches('!')))
+                .map(|rule| IgnoreRule { negated, ..rule })
+                .collect()
+        };
+        rules.extend(config_rules(&config.exclude, false));
+        rules.extend(config_rules(&config.include, true));
+
+        Self {
+            rules,
+            max_binary_bytes: config.max_binary_bytes.unwrap_or(DEFAULT_MAX_BINARY_BYTES),
+        }
+    }
+
+    /// Whether a path (relative to the app directory) is left out of checkpoints
+    fn is_ignored(&self, relative_path: &str, is_dir: bool) -> bool {
+        let relative_path = relative_path.replace('\\', "/");
+        let name = relative_path.rsplit('/').next().unwrap_or(&relative_path);
+
+        let mut ignored = if is_dir {
+            should_ignore_dir(name)
+        } else {
+            is_default_ignored_file(Path::new(name))
+        };
+
+        for rule in &self.rules {
+            if rule.matches(&relative_path, is_dir) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// The `checkpoint` section of the app's moldable.json (defaults if absent)
+fn checkpoint_config(app_dir: &Path) -> CheckpointConfig {
+    fs::read_to_string(app_dir.join("moldable.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<MoldableManifest>(&content).ok())
+        .and_then(|manifest| manifest.checkpoint)
+        .unwrap_or_default()
 }
 
-/// Scan an app directory for all source files (excluding ignored dirs and binary files)
-fn scan_source_files(app_dir: &Path) -> Result<Vec<String>, String> {
+/// Scan an app directory for all files not excluded by `rules`
+fn scan_source_files(app_dir: &Path, rules: &ScanRules) -> Result<Vec<String>, String> {
     let mut files = Vec::new();
-    
+    let relative_str = |path: &Path| -> Option<String> {
+        Some(path.strip_prefix(app_dir).ok()?.to_str()?.to_string())
+    };
+
     let walker = WalkDir::new(app_dir)
         .follow_links(false)
         .into_iter()
         .filter_entry(|entry| {
-            // Skip ignored directories
-            if entry.file_type().is_dir() {
-                if let Some(name) = entry.file_name().to_str() {
-                    return !should_ignore_dir(name);
+            // Skip ignored directories (never the app directory itself)
+            if entry.depth() > 0 && entry.file_type().is_dir() {
+                if let Some(relative) = relative_str(entry.path()) {
+                    return !rules.is_ignored(&relative, true);
                 }
             }
             true
@@ -111,17 +334,10 @@ fn scan_source_files(app_dir: &Path) -> Result<Vec<String>, String> {
             continue;
         }
         
-        let path = entry.path();
-        
-        // Skip binary files
-        if is_binary_file(path) {
-            continue;
-        }
-        
-        // Get relative path
-        if let Ok(relative) = path.strip_prefix(app_dir) {
-            if let Some(relative_str) = relative.to_str() {
-                files.push(relative_str.to_string());
+        // Skip ignored files
+        if let Some(relative) = relative_str(entry.path()) {
+            if !rules.is_ignored(&relative, false) {
+                files.push(relative);
             }
         }
     }
@@ -133,8 +349,31 @@ fn scan_source_files(app_dir: &Path) -> Result<Vec<String>, String> {
 // DATA STRUCTURES
 // ============================================================================
 
+/// What a snapshot was captured for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotKind {
+    /// Captured before an AI message modified the app
+    #[default]
+    Message,
+    /// Captured automatically right before a restore, so the restore can be undone
+    PreRestore,
+}
+
+/// How a snapshot's file is named under `snapshots/`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotLayout {
+    /// `{messageId}.json`, from before snapshot IDs were unique. Such
+    /// snapshots may share an ID, but there is only one per message.
+    #[default]
+    ByMessage,
+    /// `{snapshotId}.json`
+    ById,
+}
+
 /// Summary of a checkpoint for listing
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CheckpointSummary {
     pub id: String,
@@ -146,6 +385,23 @@ pub struct CheckpointSummary {
     /// This is computed when listing, not stored.
     #[serde(default)]
     pub has_changes: bool,
+    #[serde(default)]
+    pub kind: SnapshotKind,
+    /// Optional caller-supplied label, e.g. the tool call a checkpoint precedes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub layout: SnapshotLayout,
+}
+
+impl CheckpointSummary {
+    /// Name of the snapshot's file under `snapshots/`, unique within the conversation
+    pub(crate) fn file_key(&self) -> &str {
+        match self.layout {
+            SnapshotLayout::ByMessage => &self.message_id,
+            SnapshotLayout::ById => &self.id,
+        }
+    }
 }
 
 /// The checkpoint manifest for a conversation
@@ -173,6 +429,9 @@ pub struct FileEntry {
     pub mode: u32,
     /// Whether the file existed at snapshot time
     pub exists: bool,
+    /// Whether the content is binary (diffs don't show hunks for it)
+    #[serde(default)]
+    pub binary: bool,
 }
 
 /// A complete snapshot of files at a point in time
@@ -186,10 +445,25 @@ pub struct Snapshot {
     pub app_dir: String,
     pub created_at: DateTime<Utc>,
     pub files: Vec<FileEntry>,
+    #[serde(default)]
+    pub kind: SnapshotKind,
+    /// For pre-restore snapshots, the message whose checkpoint was being restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_message_id: Option<String>,
+    /// For pre-restore snapshots, the snapshot being restored (absent on older ones)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_snapshot_id: Option<String>,
+    /// See `CheckpointSummary::label`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// For git-backed snapshots, the commit holding their files. File hashes
+    /// are then git object IDs rather than blob store hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
 }
 
 /// Result of creating a checkpoint
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CheckpointResult {
     pub id: String,
@@ -201,16 +475,22 @@ pub struct CheckpointResult {
 }
 
 /// Result of restoring a checkpoint
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RestoreResult {
     pub files_restored: usize,
     pub files_deleted: usize,
     pub bytes_written: u64,
+    /// Message ID of the safety snapshot taken before restoring (pass to `redo_restore`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_restore_message_id: Option<String>,
+    /// Snapshot ID of the safety snapshot taken before restoring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_restore_snapshot_id: Option<String>,
 }
 
 /// Result of garbage collection
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CleanupResult {
     pub snapshots_deleted: usize,
@@ -218,6 +498,64 @@ pub struct CleanupResult {
     pub bytes_freed: u64,
 }
 
+impl std::ops::AddAssign for CleanupResult {
+    fn add_assign(&mut self, other: Self) {
+        self.snapshots_deleted += other.snapshots_deleted;
+        self.blobs_deleted += other.blobs_deleted;
+        self.bytes_freed += other.bytes_freed;
+    }
+}
+
+/// How a file changed between two states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A single hunk of a unified text diff
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// Hunk header, e.g. `@@ -1,3 +1,4 @@`
+    pub header: String,
+    /// Hunk lines prefixed with ' ', '+' or '-'
+    pub lines: Vec<String>,
+}
+
+/// Changes to a single file between two states
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    /// Path relative to app directory
+    pub path: String,
+    pub change: FileChangeKind,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    /// Either side is binary; the change is reported without hunks
+    pub binary: bool,
+    /// Unified diff hunks (empty for binary files or if either side is not valid UTF-8)
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Result of diffing a checkpoint against another checkpoint or the working tree
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointDiff {
+    pub from_snapshot_id: String,
+    pub from_message_id: String,
+    /// Target snapshot ID (None when compared against the current working tree)
+    pub to_snapshot_id: Option<String>,
+    /// Target message ID (None when compared against the current working tree)
+    pub to_message_id: Option<String>,
+    pub added: usize,
+    pub modified: usize,
+    pub deleted: usize,
+    pub files: Vec<FileDiff>,
+}
+
 // ============================================================================
 // PATH HELPERS
 // ============================================================================
@@ -228,10 +566,16 @@ pub fn get_checkpoints_dir() -> Result<PathBuf, String> {
     Ok(workspace_dir.join("checkpoints"))
 }
 
+/// Get the checkpoint directory for an app (shared by all its conversations)
+pub fn get_app_checkpoints_dir(app_id: &str) -> Result<PathBuf, String> {
+    let checkpoints_dir = get_checkpoints_dir()?;
+    Ok(checkpoints_dir.join(app_id))
+}
+
 /// Get the checkpoint directory for a specific app + conversation
 pub fn get_checkpoint_dir(app_id: &str, conversation_id: &str) -> Result<PathBuf, String> {
-    let checkpoints_dir = get_checkpoints_dir()?;
-    Ok(checkpoints_dir.join(app_id).join(conversation_id))
+    let app_checkpoints_dir = get_app_checkpoints_dir(app_id)?;
+    Ok(app_checkpoints_dir.join(conversation_id))
 }
 
 /// Get the manifest file path for a conversation
@@ -240,20 +584,39 @@ pub fn get_manifest_path(app_id: &str, conversation_id: &str) -> Result<PathBuf,
     Ok(dir.join("manifest.json"))
 }
 
-/// Get the blobs directory for a conversation
-pub fn get_blobs_dir(app_id: &str, conversation_id: &str) -> Result<PathBuf, String> {
-    let dir = get_checkpoint_dir(app_id, conversation_id)?;
-    Ok(dir.join("blobs"))
+/// Get the blob store directory for an app
+pub fn get_blob_store_dir(app_id: &str) -> Result<PathBuf, String> {
+    let app_checkpoints_dir = get_app_checkpoints_dir(app_id)?;
+    Ok(app_checkpoints_dir.join(BLOB_STORE_DIR))
+}
+
+/// Get the stat cache path for an app
+pub fn get_stat_cache_path(app_id: &str) -> Result<PathBuf, String> {
+    let app_checkpoints_dir = get_app_checkpoints_dir(app_id)?;
+    Ok(app_checkpoints_dir.join(STAT_CACHE_FILE))
 }
 
 /// Get the path for a blob by its hash
-pub fn get_blob_path(app_id: &str, conversation_id: &str, hash: &str) -> Result<PathBuf, String> {
+pub fn get_blob_path(app_id: &str, hash: &str) -> Result<PathBuf, String> {
+    let store_dir = get_blob_store_dir(app_id)?;
+    blob_path_at_path(&store_dir, hash)
+}
+
+/// Get the path for a blob inside a specific blob store directory
+pub(crate) fn blob_path_at_path(store_dir: &Path, hash: &str) -> Result<PathBuf, String> {
     if hash.len() < 2 {
         return Err("Hash too short".to_string());
     }
-    let blobs_dir = get_blobs_dir(app_id, conversation_id)?;
     let prefix = &hash[0..2];
-    Ok(blobs_dir.join(prefix).join(hash))
+    Ok(store_dir.join("blobs").join(prefix).join(hash))
+}
+
+/// Get the path for a snapshot file inside a specific conversation directory.
+/// See `CheckpointSummary::file_key`.
+fn snapshot_path_at_path(conversation_dir: &Path, file_key: &str) -> PathBuf {
+    conversation_dir
+        .join("snapshots")
+        .join(format!("{}.json", file_key))
 }
 
 /// Get the snapshots directory for a conversation
@@ -262,14 +625,113 @@ pub fn get_snapshots_dir(app_id: &str, conversation_id: &str) -> Result<PathBuf,
     Ok(dir.join("snapshots"))
 }
 
-/// Get the path for a snapshot file
+/// Get the path for a snapshot file. `file_key` is the snapshot ID, or the
+/// message ID for snapshots stored before IDs were unique.
 pub fn get_snapshot_path(
     app_id: &str,
     conversation_id: &str,
-    message_id: &str,
+    file_key: &str,
 ) -> Result<PathBuf, String> {
     let snapshots_dir = get_snapshots_dir(app_id, conversation_id)?;
-    Ok(snapshots_dir.join(format!("{}.json", message_id)))
+    Ok(snapshots_dir.join(format!("{}.json", file_key)))
+}
+
+// ============================================================================
+// LOCKING AND ATOMIC WRITES
+// ============================================================================
+
+/// Exclusive lock on an app's checkpoints, released on drop
+pub(crate) struct CheckpointLock {
+    path: PathBuf,
+}
+
+impl Drop for CheckpointLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Take the lock that serializes every read-modify-write of an app's
+/// manifests, snapshots and blob refs, across threads and Moldable windows.
+/// The blob store is shared by all conversations, so the lock is per app.
+pub(crate) fn acquire_checkpoint_lock_at_path(
+    app_checkpoints_dir: &Path,
+) -> Result<CheckpointLock, String> {
+    fs::create_dir_all(app_checkpoints_dir)
+        .map_err(|e| format!("Failed to create checkpoints directory: {}", e))?;
+
+    let lock_path = app_checkpoints_dir.join(CHECKPOINT_LOCK_FILE);
+    let start = Instant::now();
+
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => {
+                drop(file);
+                return Ok(CheckpointLock { path: lock_path });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                if is_lock_stale(&lock_path, CHECKPOINT_LOCK_STALE_AFTER) {
+                    warn!("Removing stale checkpoint lock at {}", lock_path.display());
+                    let _ = fs::remove_file(&lock_path);
+                    continue;
+                }
+                if start.elapsed() >= CHECKPOINT_LOCK_TIMEOUT {
+                    return Err("Timed out waiting for checkpoint lock".to_string());
+                }
+                std::thread::sleep(Duration::from_millis(25));
+            }
+            Err(e) => return Err(format!("Failed to create checkpoint lock: {}", e)),
+        }
+    }
+}
+
+fn acquire_checkpoint_lock(app_id: &str) -> Result<CheckpointLock, String> {
+    acquire_checkpoint_lock_at_path(&get_app_checkpoints_dir(app_id)?)
+}
+
+fn is_lock_stale(lock_path: &Path, stale_after: Duration) -> bool {
+    if let Ok(metadata) = fs::metadata(lock_path) {
+        if let Ok(modified) = metadata.modified() {
+            if let Ok(age) = SystemTime::now().duration_since(modified) {
+                return age > stale_after;
+            }
+        }
+    }
+    false
+}
+
+/// Write a file by renaming a fully written temp file over it, so neither
+/// readers nor a crash ever see it half-written. With `durable`, the data is
+/// also synced before the rename. Blobs skip that: a checkpoint can write
+/// thousands, and a torn blob is caught by `verify_checkpoints`.
+fn write_file_atomic(path: &Path, content: &[u8], durable: bool) -> Result<(), String> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("Path has no parent directory: {}", path.display()))?;
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let mut temp_file = tempfile::Builder::new()
+        .prefix(".checkpoint.tmp-")
+        .tempfile_in(parent)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    temp_file
+        .write_all(content)
+        .map_err(|e| format!("Failed to write temp file: {}", e))?;
+    if durable {
+        temp_file
+            .as_file()
+            .sync_all()
+            .map_err(|e| format!("Failed to sync temp file: {}", e))?;
+    }
+    temp_file
+        .persist(path)
+        .map_err(|e| format!("Failed to persist temp file: {}", e.error))?;
+
+    Ok(())
 }
 
 // ============================================================================
@@ -283,40 +745,35 @@ pub fn hash_content(content: &[u8]) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-/// Store a blob if it doesn't already exist
-/// Returns (hash, was_created)
-pub fn store_blob(
-    app_id: &str,
-    conversation_id: &str,
-    content: &[u8],
-) -> Result<(String, bool), String> {
-    let hash = hash_content(content);
-    let blob_path = get_blob_path(app_id, conversation_id, &hash)?;
-
-    if blob_path.exists() {
-        return Ok((hash, false));
-    }
-
-    // Ensure parent directory exists
-    if let Some(parent) = blob_path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create blob directory: {}", e))?;
-    }
-
-    fs::write(&blob_path, content).map_err(|e| format!("Failed to write blob: {}", e))?;
+/// Whether a string looks like a blob hash (lowercase hex SHA-256)
+pub(crate) fn is_valid_blob_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
 
-    Ok((hash, true))
+/// Store a blob in the app's shared store if it doesn't already exist
+/// Returns (hash, was_created)
+pub fn store_blob(app_id: &str, content: &[u8]) -> Result<(String, bool), String> {
+    let store_dir = ensure_blob_store_at_path(&get_app_checkpoints_dir(app_id)?)?;
+    store_blob_at_path(&store_dir, content)
 }
 
 /// Read a blob by its hash
-pub fn read_blob(app_id: &str, conversation_id: &str, hash: &str) -> Result<Vec<u8>, String> {
-    let blob_path = get_blob_path(app_id, conversation_id, hash)?;
+pub fn read_blob(app_id: &str, hash: &str) -> Result<Vec<u8>, String> {
+    let store_dir = ensure_blob_store_at_path(&get_app_checkpoints_dir(app_id)?)?;
+    read_blob_at_path(&store_dir, hash)
+}
 
-    if !blob_path.exists() {
-        return Err(format!("Blob not found: {}", hash));
-    }
+/// Add one reference for each blob a newly saved snapshot uses
+pub fn retain_blobs(app_id: &str, hashes: &BTreeSet<String>) -> Result<(), String> {
+    let store_dir = ensure_blob_store_at_path(&get_app_checkpoints_dir(app_id)?)?;
+    retain_blobs_at_path(&store_dir, hashes)
+}
 
-    fs::read(&blob_path).map_err(|e| format!("Failed to read blob: {}", e))
+/// Drop one reference for each blob a deleted snapshot used, deleting blobs
+/// that are no longer referenced. Returns (blobs_deleted, bytes_freed).
+pub fn release_blobs(app_id: &str, hashes: &BTreeSet<String>) -> Result<(usize, u64), String> {
+    let store_dir = ensure_blob_store_at_path(&get_app_checkpoints_dir(app_id)?)?;
+    release_blobs_at_path(&store_dir, hashes)
 }
 
 /// Load the manifest for a conversation (or create a new one)
@@ -324,49 +781,126 @@ pub fn load_or_create_manifest(
     app_id: &str,
     conversation_id: &str,
 ) -> Result<CheckpointManifest, String> {
-    let manifest_path = get_manifest_path(app_id, conversation_id)?;
+    let conversation_dir = get_checkpoint_dir(app_id, conversation_id)?;
+    load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id)
+}
 
-    if manifest_path.exists() {
-        let content = fs::read_to_string(&manifest_path)
-            .map_err(|e| format!("Failed to read manifest: {}", e))?;
-        serde_json::from_str(&content).map_err(|e| format!("Failed to parse manifest: {}", e))
-    } else {
-        Ok(CheckpointManifest {
+pub(crate) fn load_or_create_manifest_at_path(
+    conversation_dir: &Path,
+    app_id: &str,
+    conversation_id: &str,
+) -> Result<CheckpointManifest, String> {
+    match load_manifest_at_path(conversation_dir)? {
+        Some(manifest) => Ok(manifest),
+        None => Ok(CheckpointManifest {
             conversation_id: conversation_id.to_string(),
             app_id: app_id.to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
             snapshots: vec![],
-        })
+        }),
     }
 }
 
-/// Save the manifest
-pub fn save_manifest(manifest: &CheckpointManifest) -> Result<(), String> {
-    let manifest_path = get_manifest_path(&manifest.app_id, &manifest.conversation_id)?;
+/// Load a conversation's manifest, or `None` if it has never been saved
+pub(crate) fn load_manifest_at_path(
+    conversation_dir: &Path,
+) -> Result<Option<CheckpointManifest>, String> {
+    let manifest_path = conversation_dir.join("manifest.json");
 
-    // Ensure parent directory exists
-    if let Some(parent) = manifest_path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create manifest directory: {}", e))?;
+    if !manifest_path.exists() {
+        return Ok(None);
     }
 
+    let content = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read manifest: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse manifest: {}", e))
+}
+
+/// Save the manifest
+pub fn save_manifest(manifest: &CheckpointManifest) -> Result<(), String> {
+    let conversation_dir = get_checkpoint_dir(&manifest.app_id, &manifest.conversation_id)?;
+    save_manifest_at_path(&conversation_dir, manifest)
+}
+
+pub(crate) fn save_manifest_at_path(
+    conversation_dir: &Path,
+    manifest: &CheckpointManifest,
+) -> Result<(), String> {
     let content =
         serde_json::to_string_pretty(manifest).map_err(|e| format!("Failed to serialize: {}", e))?;
 
-    fs::write(&manifest_path, content).map_err(|e| format!("Failed to write manifest: {}", e))
+    write_file_atomic(&conversation_dir.join("manifest.json"), content.as_bytes(), true)
+        .map_err(|e| format!("Failed to write manifest: {}", e))
 }
 
-/// Load a snapshot by message ID
+/// Load the latest snapshot captured for a message
 pub fn load_snapshot(
     app_id: &str,
     conversation_id: &str,
     message_id: &str,
 ) -> Result<Snapshot, String> {
-    let snapshot_path = get_snapshot_path(app_id, conversation_id, message_id)?;
+    let conversation_dir = get_checkpoint_dir(app_id, conversation_id)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id)?;
+    let index = find_snapshot_index(&manifest, None, Some(message_id))?;
+    load_snapshot_at_path(&conversation_dir, manifest.snapshots[index].file_key())
+}
+
+/// Find a snapshot in a manifest by snapshot ID, or else the latest one
+/// captured for `message_id`. Returns its index in `manifest.snapshots`.
+pub(crate) fn find_snapshot_index(
+    manifest: &CheckpointManifest,
+    snapshot_id: Option<&str>,
+    message_id: Option<&str>,
+) -> Result<usize, String> {
+    let snapshots = &manifest.snapshots;
+    let found = match (snapshot_id, message_id) {
+        (Some(id), _) => snapshots.iter().rposition(|summary| summary.id == id).ok_or(id),
+        (None, Some(id)) => snapshots
+            .iter()
+            .rposition(|summary| summary.message_id == id)
+            .ok_or(id),
+        (None, None) => return Err("A snapshot ID or message ID is required".to_string()),
+    };
+
+    found.map_err(|id| format!("Snapshot not found: {}", id))
+}
+
+/// Generate an ID for a new snapshot that no snapshot in the conversation
+/// uses yet. The caller must hold the checkpoint lock.
+pub(crate) fn new_snapshot_id(
+    conversation_dir: &Path,
+    manifest: &CheckpointManifest,
+    message_id: &str,
+    created_at: DateTime<Utc>,
+) -> String {
+    let stamp = created_at.format("%Y%m%d-%H%M%S%3f");
+    let nanos = created_at.timestamp_nanos_opt().unwrap_or_default();
+    let mut attempt: u64 = 0;
+
+    loop {
+        let seed = format!("{}:{}:{}", message_id, nanos, attempt);
+        let id = format!("snap-{}-{}", stamp, &hash_content(seed.as_bytes())[..8]);
+        let taken = manifest.snapshots.iter().any(|summary| summary.id == id)
+            || snapshot_path_at_path(conversation_dir, &id).exists();
+        if !taken {
+            return id;
+        }
+        attempt += 1;
+    }
+}
+
+/// Load a snapshot file by its key (see `CheckpointSummary::file_key`)
+pub(crate) fn load_snapshot_at_path(
+    conversation_dir: &Path,
+    file_key: &str,
+) -> Result<Snapshot, String> {
+    let snapshot_path = snapshot_path_at_path(conversation_dir, file_key);
 
     if !snapshot_path.exists() {
-        return Err(format!("Snapshot not found: {}", message_id));
+        return Err(format!("Snapshot not found: {}", file_key));
     }
 
     let content =
@@ -375,198 +909,1640 @@ pub fn load_snapshot(
     serde_json::from_str(&content).map_err(|e| format!("Failed to parse snapshot: {}", e))
 }
 
-/// Save a snapshot
+/// Save a snapshot under its ID
 pub fn save_snapshot(snapshot: &Snapshot) -> Result<(), String> {
-    let snapshot_path =
-        get_snapshot_path(&snapshot.app_id, &snapshot.conversation_id, &snapshot.message_id)?;
+    let conversation_dir = get_checkpoint_dir(&snapshot.app_id, &snapshot.conversation_id)?;
+    save_snapshot_at_path(&conversation_dir, snapshot)
+}
 
-    // Ensure parent directory exists
-    if let Some(parent) = snapshot_path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create snapshots directory: {}", e))?;
-    }
+pub(crate) fn save_snapshot_at_path(
+    conversation_dir: &Path,
+    snapshot: &Snapshot,
+) -> Result<(), String> {
+    let snapshot_path = snapshot_path_at_path(conversation_dir, &snapshot.id);
 
     let content =
         serde_json::to_string_pretty(snapshot).map_err(|e| format!("Failed to serialize: {}", e))?;
 
-    fs::write(&snapshot_path, content).map_err(|e| format!("Failed to write snapshot: {}", e))
+    write_file_atomic(&snapshot_path, content.as_bytes(), true)
+        .map_err(|e| format!("Failed to write snapshot: {}", e))
 }
 
-/// Validate that a path is within the app directory (security check)
-pub fn validate_path_in_app_dir(app_dir: &Path, relative_path: &str) -> Result<PathBuf, String> {
-    let relative = Path::new(relative_path);
+// ============================================================================
+// SHARED BLOB STORE
+// ============================================================================
 
-    if relative.is_absolute() {
-        return Err(format!("Path must be relative: {}", relative_path));
+/// Reference counts for an app's blob store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobRefs {
+    /// Blob hash -> number of snapshots that reference it
+    pub refs: BTreeMap<String, usize>,
+}
+
+/// Distinct blob hashes referenced by a snapshot. Git-backed snapshots keep
+/// their content in the app's repository and reference no blobs.
+pub(crate) fn snapshot_blob_hashes(snapshot: &Snapshot) -> BTreeSet<String> {
+    if snapshot.git_commit.is_some() {
+        return BTreeSet::new();
     }
 
-    for component in relative.components() {
-        match component {
-            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
-                return Err(format!("Path contains invalid component: {}", relative_path));
-            }
-            _ => {}
-        }
+    snapshot
+        .files
+        .iter()
+        .filter_map(|f| f.hash.clone())
+        .collect()
+}
+
+/// Make sure an app's blob store exists, migrating legacy per-conversation
+/// blob directories the first time it's used. Returns the store directory.
+pub(crate) fn ensure_blob_store_at_path(app_checkpoints_dir: &Path) -> Result<PathBuf, String> {
+    let store_dir = app_checkpoints_dir.join(BLOB_STORE_DIR);
+    if !store_dir.join(BLOB_REFS_FILE).exists() {
+        migrate_legacy_blobs_at_path(app_checkpoints_dir)?;
     }
+    Ok(store_dir)
+}
 
-    // Resolve the full path
-    let full_path = app_dir.join(relative);
+/// Non-hidden subdirectories: apps inside the checkpoints directory, or
+/// conversations inside an app's checkpoint directory
+fn checkpoint_subdirs(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
 
-    // Canonicalize both paths to resolve any .. or symlinks
-    // Note: For non-existent files, we check the parent
-    let canonical_app_dir = app_dir
-        .canonicalize()
-        .map_err(|e| format!("Invalid app directory: {}", e))?;
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read checkpoints directory: {}", e))?;
 
-    // Check the nearest existing ancestor to prevent symlink escapes
-    let mut cursor = full_path.clone();
-    loop {
-        if cursor.exists() {
-            let canonical_cursor = cursor
-                .canonicalize()
-                .map_err(|e| format!("Invalid path: {}", e))?;
-            if !canonical_cursor.starts_with(&canonical_app_dir) {
-                return Err(format!("Path escapes app directory: {}", relative_path));
-            }
-            break;
-        }
+    Ok(entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect())
+}
 
-        let parent = match cursor.parent() {
-            Some(p) => p,
-            None => break,
-        };
+/// Move blobs from the old `{conversationId}/blobs` layout into the app's
+/// shared store, then rebuild reference counts from every snapshot.
+/// Returns the number of blobs moved.
+fn migrate_legacy_blobs_at_path(app_checkpoints_dir: &Path) -> Result<usize, String> {
+    let store_dir = app_checkpoints_dir.join(BLOB_STORE_DIR);
+    let mut moved = 0;
 
-        // Stop if we can no longer ascend
-        if parent == cursor {
-            break;
+    for conversation_dir in checkpoint_subdirs(app_checkpoints_dir)? {
+        let legacy_blobs_dir = conversation_dir.join("blobs");
+        if !legacy_blobs_dir.is_dir() {
+            continue;
         }
-        cursor = parent.to_path_buf();
-    }
 
-    Ok(full_path)
-}
+        for entry in WalkDir::new(&legacy_blobs_dir).min_depth(2).max_depth(2) {
+            let entry = entry.map_err(|e| format!("Failed to read legacy blobs: {}", e))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let hash = entry.file_name().to_string_lossy().to_string();
+            let target = match blob_path_at_path(&store_dir, &hash) {
+                Ok(p) => p,
+                Err(_) => continue, // Not a blob
+            };
+
+            // Another conversation already contributed this blob
+            if target.exists() {
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create blob directory: {}", e))?;
+            }
+            fs::rename(entry.path(), &target)
+                .map_err(|e| format!("Failed to move blob {}: {}", hash, e))?;
+            moved += 1;
+        }
+
+        fs::remove_dir_all(&legacy_blobs_dir)
+            .map_err(|e| format!("Failed to remove legacy blobs directory: {}", e))?;
+    }
+
+    let refs = rebuild_blob_refs_at_path(app_checkpoints_dir)?;
+    save_blob_refs_at_path(&store_dir, &refs)?;
+
+    if moved > 0 {
+        info!(
+            "Migrated {} checkpoint blobs into shared store at {:?}",
+            moved, store_dir
+        );
+    }
+
+    Ok(moved)
+}
+
+/// Count blob references across every snapshot of every conversation of an app
+fn rebuild_blob_refs_at_path(app_checkpoints_dir: &Path) -> Result<BlobRefs, String> {
+    let mut refs = BlobRefs::default();
+
+    for conversation_dir in checkpoint_subdirs(app_checkpoints_dir)? {
+        let snapshots_dir = conversation_dir.join("snapshots");
+        let entries = match fs::read_dir(&snapshots_dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let snapshot: Snapshot = match fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+            {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+
+            for hash in snapshot_blob_hashes(&snapshot) {
+                *refs.refs.entry(hash).or_insert(0) += 1;
+            }
+        }
+    }
+
+    Ok(refs)
+}
+
+fn load_blob_refs_at_path(store_dir: &Path) -> Result<BlobRefs, String> {
+    let refs_path = store_dir.join(BLOB_REFS_FILE);
+    if !refs_path.exists() {
+        return Ok(BlobRefs::default());
+    }
+
+    let content = fs::read_to_string(&refs_path)
+        .map_err(|e| format!("Failed to read blob refs: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse blob refs: {}", e))
+}
+
+fn save_blob_refs_at_path(store_dir: &Path, refs: &BlobRefs) -> Result<(), String> {
+    let content =
+        serde_json::to_string_pretty(refs).map_err(|e| format!("Failed to serialize: {}", e))?;
+
+    write_file_atomic(&store_dir.join(BLOB_REFS_FILE), content.as_bytes(), true)
+        .map_err(|e| format!("Failed to write blob refs: {}", e))
+}
+
+/// Encode blob content for disk: codec header followed by the (possibly compressed) bytes
+fn encode_blob(content: &[u8]) -> Result<Vec<u8>, String> {
+    let compressed = zstd::encode_all(content, BLOB_ZSTD_LEVEL)
+        .map_err(|e| format!("Failed to compress blob: {}", e))?;
+
+    let (codec, payload) = if compressed.len() < content.len() {
+        (BLOB_CODEC_ZSTD, compressed.as_slice())
+    } else {
+        (BLOB_CODEC_RAW, content)
+    };
+
+    let mut encoded = Vec::with_capacity(BLOB_MAGIC.len() + 1 + payload.len());
+    encoded.extend_from_slice(BLOB_MAGIC);
+    encoded.push(codec);
+    encoded.extend_from_slice(payload);
+    Ok(encoded)
+}
+
+/// Decode blob bytes read from disk back into the original content.
+/// Data without a codec header is a legacy raw blob and is returned unchanged.
+fn decode_blob(stored: Vec<u8>) -> Result<Vec<u8>, String> {
+    let header_len = BLOB_MAGIC.len() + 1;
+    if stored.len() < header_len || &stored[..BLOB_MAGIC.len()] != BLOB_MAGIC {
+        return Ok(stored);
+    }
+
+    let payload = &stored[header_len..];
+    match stored[BLOB_MAGIC.len()] {
+        BLOB_CODEC_RAW => Ok(payload.to_vec()),
+        BLOB_CODEC_ZSTD => zstd::decode_all(payload)
+            .map_err(|e| format!("Failed to decompress blob: {}", e)),
+        // A legacy blob whose content happens to start with the magic bytes
+        _ => Ok(stored),
+    }
+}
+
+pub(crate) fn store_blob_at_path(
+    store_dir: &Path,
+    content: &[u8],
+) -> Result<(String, bool), String> {
+    let hash = hash_content(content);
+    let blob_path = blob_path_at_path(store_dir, &hash)?;
+
+    if blob_path.exists() {
+        return Ok((hash, false));
+    }
+
+    let encoded = encode_blob(content)?;
+    write_file_atomic(&blob_path, &encoded, false)
+        .map_err(|e| format!("Failed to write blob: {}", e))?;
+
+    Ok((hash, true))
+}
+
+pub(crate) fn read_blob_at_path(store_dir: &Path, hash: &str) -> Result<Vec<u8>, String> {
+    let blob_path = blob_path_at_path(store_dir, hash)?;
+
+    if !blob_path.exists() {
+        return Err(format!("Blob not found: {}", hash));
+    }
+
+    let stored = fs::read(&blob_path).map_err(|e| format!("Failed to read blob: {}", e))?;
+    decode_blob(stored)
+}
+
+pub(crate) fn retain_blobs_at_path(
+    store_dir: &Path,
+    hashes: &BTreeSet<String>,
+) -> Result<(), String> {
+    if hashes.is_empty() {
+        return Ok(());
+    }
+
+    let mut refs = load_blob_refs_at_path(store_dir)?;
+    for hash in hashes {
+        *refs.refs.entry(hash.clone()).or_insert(0) += 1;
+    }
+    save_blob_refs_at_path(store_dir, &refs)
+}
+
+fn release_blobs_at_path(
+    store_dir: &Path,
+    hashes: &BTreeSet<String>,
+) -> Result<(usize, u64), String> {
+    if hashes.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let mut refs = load_blob_refs_at_path(store_dir)?;
+    let mut blobs_deleted = 0;
+    let mut bytes_freed = 0u64;
+
+    for hash in hashes {
+        // Unknown hashes are left alone rather than risk deleting a live blob
+        let count = match refs.refs.get_mut(hash) {
+            Some(count) => count,
+            None => continue,
+        };
+
+        *count = count.saturating_sub(1);
+        if *count > 0 {
+            continue;
+        }
+        refs.refs.remove(hash);
+
+        if let Ok(path) = blob_path_at_path(store_dir, hash) {
+            if let Ok(metadata) = path.metadata() {
+                if fs::remove_file(&path).is_ok() {
+                    bytes_freed += metadata.len();
+                    blobs_deleted += 1;
+                }
+            }
+        }
+    }
+
+    save_blob_refs_at_path(store_dir, &refs)?;
+    Ok((blobs_deleted, bytes_freed))
+}
 
 // ============================================================================
-// TAURI COMMANDS
-// ============================================================================
+// STAT CACHE
+// ============================================================================
 
-/// Create a checkpoint by scanning all source files in the app directory.
-/// This captures the complete state of the app before an AI response.
-#[tauri::command]
-pub async fn create_checkpoint(
-    app_id: String,
-    app_dir: String,
-    conversation_id: String,
-    message_id: String,
-) -> Result<CheckpointResult, String> {
-    let app_dir_path = PathBuf::from(&app_dir);
+/// File metadata used to tell whether a cached hash is still valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileStat {
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    /// Inode number (0 where unavailable)
+    pub inode: u64,
+}
+
+impl FileStat {
+    fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        #[cfg(unix)]
+        let inode = {
+            use std::os::unix::fs::MetadataExt;
+            metadata.ino()
+        };
+        #[cfg(not(unix))]
+        let inode = 0u64;
+
+        Some(Self {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            inode,
+        })
+    }
+
+    fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(self.mtime_secs, self.mtime_nanos)
+    }
+}
+
+/// A cached content hash for one working-tree file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatCacheEntry {
+    pub stat: FileStat,
+    pub hash: String,
+    #[serde(default)]
+    pub binary: bool,
+}
+
+/// Persisted map of relative path -> (stat, hash) for an app's working tree
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatCache {
+    /// App directory the entries were computed for
+    pub app_dir: String,
+    pub entries: BTreeMap<String, StatCacheEntry>,
+}
+
+impl StatCache {
+    fn new(app_dir: &Path) -> Self {
+        Self {
+            app_dir: app_dir.to_string_lossy().to_string(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Cached entry for a file, if its stat hasn't changed since it was hashed
+    fn lookup(&self, relative_path: &str, stat: &FileStat) -> Option<&StatCacheEntry> {
+        self.entries
+            .get(relative_path)
+            .filter(|entry| entry.stat == *stat)
+    }
+
+    /// Remember a file's hash unless it was modified too close to `scan_started`
+    fn record(
+        &mut self,
+        relative_path: &str,
+        stat: FileStat,
+        hash: &str,
+        binary: bool,
+        scan_started: SystemTime,
+    ) {
+        let settled = scan_started
+            .duration_since(stat.modified())
+            .is_ok_and(|age| age >= STAT_CACHE_RACY_WINDOW);
+        if settled {
+            self.entries.insert(
+                relative_path.to_string(),
+                StatCacheEntry {
+                    stat,
+                    hash: hash.to_string(),
+                    binary,
+                },
+            );
+        }
+    }
+}
+
+/// Load the stat cache for `app_dir`. A missing, unreadable, or mismatched
+/// cache yields an empty one, which simply means every file is hashed.
+fn load_stat_cache_at_path(cache_path: &Path, app_dir: &Path) -> StatCache {
+    let cache: Option<StatCache> = fs::read_to_string(cache_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+
+    match cache {
+        Some(cache) if Path::new(&cache.app_dir) == app_dir => cache,
+        _ => StatCache::new(app_dir),
+    }
+}
+
+fn save_stat_cache_at_path(cache_path: &Path, cache: &StatCache) -> Result<(), String> {
+    let content =
+        serde_json::to_string(cache).map_err(|e| format!("Failed to serialize: {}", e))?;
+
+    // Only a cache: losing it to a crash just means re-hashing, so no fsync
+    write_file_atomic(cache_path, content.as_bytes(), false)
+        .map_err(|e| format!("Failed to write stat cache: {}", e))
+}
+
+/// Result of scanning an app's working tree
+#[derive(Debug, Default)]
+struct WorkingTreeScan {
+    files: Vec<FileEntry>,
+    total_bytes: u64,
+    blobs_created: usize,
+    blobs_reused: usize,
+    /// Files that had to be read and hashed (cache misses)
+    files_hashed: usize,
+    /// Fresh cache reflecting this scan
+    cache: StatCache,
+}
+
+/// Hash every source file in `app_dir`, reusing hashes from `previous` for files
+/// whose stat is unchanged. When `store_dir` is given, content is also written
+/// to the blob store and the checkpoint size limit applies.
+fn scan_working_tree(
+    app_dir: &Path,
+    previous: &StatCache,
+    store_dir: Option<&Path>,
+) -> Result<WorkingTreeScan, String> {
+    let scan_started = SystemTime::now();
+    let rules = ScanRules::for_app_dir(app_dir);
+    let source_files = scan_source_files(app_dir, &rules)?;
+    let mut scan = WorkingTreeScan {
+        cache: StatCache::new(app_dir),
+        ..Default::default()
+    };
+
+    for relative_path in &source_files {
+        let full_path = validate_path_in_app_dir(app_dir, relative_path)?;
+
+        let metadata = match full_path.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue, // Removed since the directory scan
+        };
+        let size = metadata.len();
+
+        // Skip files that are too large (10MB limit per file, less for binaries)
+        let known_binary = is_binary_file(Path::new(relative_path));
+        if size > MAX_FILE_BYTES || (known_binary && size > rules.max_binary_bytes) {
+            continue;
+        }
+
+        if store_dir.is_some() {
+            // Enforce total size limit (100MB per checkpoint)
+            if scan.total_bytes + size > MAX_CHECKPOINT_BYTES {
+                break;
+            }
+        }
+
+        let stat = FileStat::from_metadata(&metadata);
+        let cached = stat
+            .as_ref()
+            .and_then(|stat| previous.lookup(relative_path, stat))
+            .filter(|entry| match store_dir {
+                // Oversized binaries are skipped below and never have a blob
+                Some(_) if entry.binary && size > rules.max_binary_bytes => true,
+                Some(store_dir) => {
+                    blob_path_at_path(store_dir, &entry.hash).is_ok_and(|p| p.exists())
+                }
+                None => true,
+            })
+            .cloned();
+
+        let (hash, binary) = match cached {
+            Some(entry) => {
+                if entry.binary && size > rules.max_binary_bytes {
+                    continue;
+                }
+                if store_dir.is_some() {
+                    scan.blobs_reused += 1;
+                }
+                (entry.hash, entry.binary)
+            }
+            None => {
+                let content =
+                    fs::read(&full_path).map_err(|e| format!("Failed to read file: {}", e))?;
+                scan.files_hashed += 1;
+
+                let binary = known_binary || looks_binary(&content);
+                if binary && size > rules.max_binary_bytes {
+                    // Remember it's binary so later scans skip it without reading
+                    if let Some(stat) = stat {
+                        let hash = hash_content(&content);
+                        scan.cache.record(relative_path, stat, &hash, true, scan_started);
+                    }
+                    continue;
+                }
+
+                let hash = match store_dir {
+                    Some(store_dir) => {
+                        let (hash, was_created) = store_blob_at_path(store_dir, &content)?;
+                        if was_created {
+                            scan.blobs_created += 1;
+                        } else {
+                            scan.blobs_reused += 1;
+                        }
+                        hash
+                    }
+                    None => hash_content(&content),
+                };
+                (hash, binary)
+            }
+        };
+        scan.total_bytes += size;
+
+        if let Some(stat) = stat {
+            scan.cache.record(relative_path, stat, &hash, binary, scan_started);
+        }
+
+        // Get file mode (Unix permissions)
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::MetadataExt;
+            metadata.mode()
+        };
+        #[cfg(not(unix))]
+        let mode = 0o644u32;
+
+        scan.files.push(FileEntry {
+            path: relative_path.clone(),
+            hash: Some(hash),
+            size,
+            mode,
+            exists: true,
+            binary,
+        });
+    }
+
+    Ok(scan)
+}
+
+/// Scan an app's working tree using its persisted stat cache, saving the
+/// refreshed cache afterwards. With `full_hash`, the cache is ignored and every
+/// file is re-read (the cache is still rebuilt from the result).
+fn scan_working_tree_cached(
+    app_id: &str,
+    app_dir: &Path,
+    store: bool,
+    full_hash: bool,
+) -> Result<WorkingTreeScan, String> {
+    let app_checkpoints_dir = get_app_checkpoints_dir(app_id)?;
+    scan_working_tree_cached_at_path(&app_checkpoints_dir, app_dir, store, full_hash)
+}
+
+fn scan_working_tree_cached_at_path(
+    app_checkpoints_dir: &Path,
+    app_dir: &Path,
+    store: bool,
+    full_hash: bool,
+) -> Result<WorkingTreeScan, String> {
+    let cache_path = app_checkpoints_dir.join(STAT_CACHE_FILE);
+    let previous = if full_hash {
+        StatCache::new(app_dir)
+    } else {
+        load_stat_cache_at_path(&cache_path, app_dir)
+    };
+
+    let store_dir = if store {
+        Some(ensure_blob_store_at_path(app_checkpoints_dir)?)
+    } else {
+        None
+    };
+
+    let scan = scan_working_tree(app_dir, &previous, store_dir.as_deref())?;
+
+    // A stale cache only costs re-hashing, so failing to save it isn't fatal
+    if let Err(e) = save_stat_cache_at_path(&cache_path, &scan.cache) {
+        warn!("Failed to save checkpoint stat cache: {}", e);
+    }
+
+    Ok(scan)
+}
+
+/// Where a snapshot's file content is read from
+enum ContentSource {
+    BlobStore(PathBuf),
+    Git(GitRepo),
+}
+
+impl ContentSource {
+    fn for_snapshot(app_checkpoints_dir: &Path, snapshot: &Snapshot) -> Result<Self, String> {
+        if snapshot.git_commit.is_none() {
+            return Ok(Self::BlobStore(app_checkpoints_dir.join(BLOB_STORE_DIR)));
+        }
+
+        GitRepo::discover(Path::new(&snapshot.app_dir))
+            .map(Self::Git)
+            .ok_or_else(|| {
+                format!(
+                    "Checkpoint {} is stored in git, but {} is no longer a git repository",
+                    snapshot.id, snapshot.app_dir
+                )
+            })
+    }
+
+    fn read(&self, hash: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::BlobStore(store_dir) => read_blob_at_path(store_dir, hash),
+            Self::Git(repo) => repo.read_blob(hash),
+        }
+    }
+}
+
+/// The repository to commit checkpoints to, if the app asks for git-backed
+/// checkpoints. Falls back to the blob store (`None`) when the app isn't in a
+/// git repository or the conversation ID can't be used in a ref name.
+fn git_checkpoint_repo(app_dir: &Path, conversation_id: &str) -> Option<GitRepo> {
+    if checkpoint_config(app_dir).storage != CheckpointStorage::Git {
+        return None;
+    }
+
+    let Some(repo) = GitRepo::discover(app_dir) else {
+        warn!(
+            "{} is not in a git repository, using the checkpoint blob store",
+            app_dir.display()
+        );
+        return None;
+    };
+    if repo.checkpoint_ref(conversation_id).is_none() {
+        warn!(
+            "Conversation {} can't be a git ref, using the checkpoint blob store",
+            conversation_id
+        );
+        return None;
+    }
+
+    Some(repo)
+}
+
+/// Scan the working tree of `snapshot`'s app, hashing files the same way the
+/// snapshot did (git object IDs for git-backed snapshots) so the two compare
+fn scan_working_tree_like(
+    app_id: &str,
+    app_dir: &Path,
+    snapshot: &Snapshot,
+    full_hash: bool,
+) -> Result<Vec<FileEntry>, String> {
+    let mut files = scan_working_tree_cached(app_id, app_dir, false, full_hash)?.files;
+
+    if snapshot.git_commit.is_some() {
+        let repo = GitRepo::discover(app_dir)
+            .ok_or_else(|| format!("Not a git repository: {}", app_dir.display()))?;
+        repo.hash_files(&mut files, false)?;
+    }
+
+    Ok(files)
+}
+
+/// Check that a path is relative and cannot climb out of its base directory.
+/// Unlike `validate_path_in_app_dir`, this does not touch the filesystem.
+pub(crate) fn validate_relative_path(relative_path: &str) -> Result<(), String> {
+    let relative = Path::new(relative_path);
+
+    if relative_path.is_empty() {
+        return Err("Path is empty".to_string());
+    }
+
+    if relative.is_absolute() {
+        return Err(format!("Path must be relative: {}", relative_path));
+    }
+
+    for component in relative.components() {
+        match component {
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("Path contains invalid component: {}", relative_path));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that an id can be used as a single file or directory name
+pub(crate) fn validate_id(kind: &str, id: &str) -> Result<(), String> {
+    validate_relative_path(id).map_err(|e| format!("Invalid {} '{}': {}", kind, id, e))?;
+
+    let mut components = Path::new(id).components();
+    let single_normal = matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none();
+    if !single_normal || id.contains(['/', '\\']) {
+        return Err(format!("Invalid {} '{}': must be a plain name", kind, id));
+    }
+
+    Ok(())
+}
+
+/// Validate that a path is within the app directory (security check)
+pub fn validate_path_in_app_dir(app_dir: &Path, relative_path: &str) -> Result<PathBuf, String> {
+    validate_relative_path(relative_path)?;
+    let relative = Path::new(relative_path);
+
+    // Resolve the full path
+    let full_path = app_dir.join(relative);
+
+    // Canonicalize both paths to resolve any .. or symlinks
+    // Note: For non-existent files, we check the parent
+    let canonical_app_dir = app_dir
+        .canonicalize()
+        .map_err(|e| format!("Invalid app directory: {}", e))?;
+
+    // Check the nearest existing ancestor to prevent symlink escapes
+    let mut cursor = full_path.clone();
+    loop {
+        if cursor.exists() {
+            let canonical_cursor = cursor
+                .canonicalize()
+                .map_err(|e| format!("Invalid path: {}", e))?;
+            if !canonical_cursor.starts_with(&canonical_app_dir) {
+                return Err(format!("Path escapes app directory: {}", relative_path));
+            }
+            break;
+        }
+
+        let parent = match cursor.parent() {
+            Some(p) => p,
+            None => break,
+        };
+
+        // Stop if we can no longer ascend
+        if parent == cursor {
+            break;
+        }
+        cursor = parent.to_path_buf();
+    }
+
+    Ok(full_path)
+}
+
+// ============================================================================
+// DIFF HELPERS
+// ============================================================================
+
+/// Content hash and binary flag of one file on one side of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileVersion {
+    hash: String,
+    binary: bool,
+}
+
+/// Map of relative path -> file version for existing files in a snapshot or scan
+fn file_version_map(files: &[FileEntry]) -> BTreeMap<String, FileVersion> {
+    files
+        .iter()
+        .filter(|f| f.exists)
+        .filter_map(|f| {
+            let version = FileVersion {
+                hash: f.hash.clone()?,
+                binary: f.binary,
+            };
+            Some((f.path.clone(), version))
+        })
+        .collect()
+}
+
+/// Compute unified diff hunks between two file contents.
+/// Returns no hunks if either side is not valid UTF-8.
+fn unified_hunks(old: &[u8], new: &[u8]) -> Vec<DiffHunk> {
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return vec![];
+    };
+
+    let diff = TextDiff::from_lines(old, new);
+    let mut unified = diff.unified_diff();
+    unified.context_radius(3);
+
+    unified
+        .iter_hunks()
+        .map(|hunk| DiffHunk {
+            header: hunk.header().to_string(),
+            lines: hunk
+                .iter_changes()
+                .map(|change| {
+                    let sign = match change.tag() {
+                        ChangeTag::Delete => '-',
+                        ChangeTag::Insert => '+',
+                        ChangeTag::Equal => ' ',
+                    };
+                    format!("{}{}", sign, change.value().trim_end_matches(['\r', '\n']))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Compare two path -> hash maps and produce per-file diffs (sorted by path).
+/// `load_from` / `load_to` fetch the content for a (path, hash) pair on each side.
+fn diff_file_maps(
+    from: &BTreeMap<String, FileVersion>,
+    to: &BTreeMap<String, FileVersion>,
+    load_from: impl Fn(&str, &str) -> Result<Vec<u8>, String>,
+    load_to: impl Fn(&str, &str) -> Result<Vec<u8>, String>,
+) -> Result<Vec<FileDiff>, String> {
+    let paths: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    let mut diffs = Vec::new();
+
+    for path in paths {
+        let (old, new) = (from.get(path), to.get(path));
+        let change = match (old, new) {
+            (Some(old), Some(new)) if old.hash == new.hash => continue,
+            (Some(_), Some(_)) => FileChangeKind::Modified,
+            (None, Some(_)) => FileChangeKind::Added,
+            (Some(_), None) => FileChangeKind::Deleted,
+            (None, None) => continue,
+        };
+
+        // Binary content is reported as changed without loading it
+        let binary = old.is_some_and(|v| v.binary) || new.is_some_and(|v| v.binary);
+        let hunks = if binary {
+            vec![]
+        } else {
+            let old_content = match old {
+                Some(v) => load_from(path, &v.hash)?,
+                None => vec![],
+            };
+            let new_content = match new {
+                Some(v) => load_to(path, &v.hash)?,
+                None => vec![],
+            };
+            unified_hunks(&old_content, &new_content)
+        };
+
+        diffs.push(FileDiff {
+            path: path.clone(),
+            change,
+            old_hash: old.map(|v| v.hash.clone()),
+            new_hash: new.map(|v| v.hash.clone()),
+            binary,
+            hunks,
+        });
+    }
+
+    Ok(diffs)
+}
+
+// ============================================================================
+// RETENTION
+// ============================================================================
+
+/// Shared preference holding the checkpoint retention policy
+pub const RETENTION_PREFERENCE_KEY: &str = "checkpointRetention";
+
+const DEFAULT_KEEP_LAST_N: usize = 50;
+const DEFAULT_MAX_AGE_DAYS: u64 = 30;
+
+/// Delay before the first background collection, to stay out of startup's way
+const GC_INITIAL_DELAY: Duration = Duration::from_secs(5 * 60);
+const GC_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Which snapshots the garbage collector keeps, read from the
+/// `checkpointRetention` shared preference.
+///
+/// A snapshot survives if it is among the last `keepLastN` of its conversation
+/// or newer than `maxAgeDays`; set either to `null` to drop that rule. The byte
+/// caps then delete the oldest remaining snapshots until the app's (or the
+/// whole workspace's) blob store fits, but never a conversation's latest one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    pub keep_last_n: Option<usize>,
+    pub max_age_days: Option<u64>,
+    pub max_bytes_per_app: Option<u64>,
+    pub max_bytes_per_workspace: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last_n: Some(DEFAULT_KEEP_LAST_N),
+            max_age_days: Some(DEFAULT_MAX_AGE_DAYS),
+            max_bytes_per_app: None,
+            max_bytes_per_workspace: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Load the policy from shared preferences, falling back to the defaults
+    pub fn load() -> Self {
+        let config = load_shared_config();
+        let value = match config.preferences.get(RETENTION_PREFERENCE_KEY) {
+            Some(value) => value.clone(),
+            None => return Self::default(),
+        };
+
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            warn!("Invalid {} preference, using defaults: {}", RETENTION_PREFERENCE_KEY, e);
+            Self::default()
+        })
+    }
+
+    /// Whether the count and age rules let a snapshot go.
+    /// `rank` counts back from the conversation's newest snapshot (0 = newest).
+    fn allows_removal(&self, rank: usize, created_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        if self.keep_last_n.is_none() && self.max_age_days.is_none() {
+            return false;
+        }
+
+        let kept_by_count = self.keep_last_n.is_some_and(|n| rank < n);
+        let kept_by_age = self.max_age_days.is_some_and(|max_days| {
+            // Snapshots dated in the future (clock skew) are kept
+            u64::try_from((now - created_at).num_days()).map_or(true, |age| age < max_days)
+        });
+
+        !kept_by_count && !kept_by_age
+    }
+}
+
+/// Delete the given snapshots of a conversation (by `file_key`) and release
+/// their blobs
+fn remove_snapshots_at_path(
+    conversation_dir: &Path,
+    store_dir: &Path,
+    manifest: &mut CheckpointManifest,
+    file_keys: &HashSet<String>,
+) -> Result<CleanupResult, String> {
+    let mut result = CleanupResult::default();
+    if file_keys.is_empty() {
+        return Ok(result);
+    }
+
+    for summary in &manifest.snapshots {
+        if !file_keys.contains(summary.file_key()) {
+            continue;
+        }
+
+        let snapshot = load_snapshot_at_path(conversation_dir, summary.file_key()).ok();
+
+        let path = snapshot_path_at_path(conversation_dir, summary.file_key());
+        if path.exists() {
+            fs::remove_file(&path).ok();
+            result.snapshots_deleted += 1;
+        }
+
+        if let Some(snapshot) = snapshot {
+            let (deleted, freed) =
+                release_blobs_at_path(store_dir, &snapshot_blob_hashes(&snapshot))?;
+            result.blobs_deleted += deleted;
+            result.bytes_freed += freed;
+        }
+    }
+
+    manifest
+        .snapshots
+        .retain(|summary| !file_keys.contains(summary.file_key()));
+    manifest.updated_at = Utc::now();
+    save_manifest_at_path(conversation_dir, manifest)?;
+
+    Ok(result)
+}
+
+/// Bytes used on disk by an app's blob store
+fn blob_store_bytes_at_path(store_dir: &Path) -> u64 {
+    WalkDir::new(store_dir.join("blobs"))
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// A snapshot the byte caps may delete
+struct GcCandidate {
+    created_at: DateTime<Utc>,
+    app_index: usize,
+    conversation_dir: PathBuf,
+    file_key: String,
+    removed: bool,
+}
+
+fn remove_gc_candidate(
+    candidate: &mut GcCandidate,
+    store_dir: &Path,
+) -> Result<CleanupResult, String> {
+    candidate.removed = true;
+    let app_checkpoints_dir = candidate
+        .conversation_dir
+        .parent()
+        .ok_or_else(|| "Conversation directory has no parent".to_string())?;
+    let _lock = acquire_checkpoint_lock_at_path(app_checkpoints_dir)?;
+    let mut manifest = match load_manifest_at_path(&candidate.conversation_dir)? {
+        Some(manifest) => manifest,
+        None => return Ok(CleanupResult::default()),
+    };
+    let file_keys = HashSet::from([candidate.file_key.clone()]);
+    remove_snapshots_at_path(&candidate.conversation_dir, store_dir, &mut manifest, &file_keys)
+}
+
+/// Apply a retention policy to every conversation of every app under
+/// `checkpoints_dir`. A conversation or app that fails is logged and skipped.
+fn collect_garbage_at_path(
+    checkpoints_dir: &Path,
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Result<CleanupResult, String> {
+    let mut result = CleanupResult::default();
+    // (blob store, bytes on disk) per app
+    let mut apps: Vec<(PathBuf, u64)> = Vec::new();
+    let mut candidates = Vec::new();
+
+    for app_checkpoints_dir in checkpoint_subdirs(checkpoints_dir)? {
+        let _lock = match acquire_checkpoint_lock_at_path(&app_checkpoints_dir) {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!("Skipping checkpoints in {}: {}", app_checkpoints_dir.display(), e);
+                continue;
+            }
+        };
+        let store_dir = match ensure_blob_store_at_path(&app_checkpoints_dir) {
+            Ok(store_dir) => store_dir,
+            Err(e) => {
+                warn!("Skipping checkpoints in {}: {}", app_checkpoints_dir.display(), e);
+                continue;
+            }
+        };
+        let app_index = apps.len();
+
+        for conversation_dir in checkpoint_subdirs(&app_checkpoints_dir)? {
+            let mut manifest = match load_manifest_at_path(&conversation_dir) {
+                Ok(Some(manifest)) => manifest,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Skipping checkpoints in {}: {}", conversation_dir.display(), e);
+                    continue;
+                }
+            };
+
+            let expired: HashSet<String> = manifest
+                .snapshots
+                .iter()
+                .rev()
+                .enumerate()
+                .filter(|(rank, summary)| policy.allows_removal(*rank, summary.created_at, now))
+                .map(|(_, summary)| summary.file_key().to_string())
+                .collect();
+            match remove_snapshots_at_path(&conversation_dir, &store_dir, &mut manifest, &expired)
+            {
+                Ok(removed) => result += removed,
+                Err(e) => {
+                    warn!("Failed to clean up {}: {}", conversation_dir.display(), e);
+                    continue;
+                }
+            }
+
+            // Everything but the latest snapshot is fair game for the byte caps
+            for summary in manifest.snapshots.iter().rev().skip(1) {
+                candidates.push(GcCandidate {
+                    created_at: summary.created_at,
+                    app_index,
+                    conversation_dir: conversation_dir.clone(),
+                    file_key: summary.file_key().to_string(),
+                    removed: false,
+                });
+            }
+        }
+
+        let usage = blob_store_bytes_at_path(&store_dir);
+        apps.push((store_dir, usage));
+    }
+
+    candidates.sort_by_key(|candidate| candidate.created_at);
+
+    if let Some(max_bytes) = policy.max_bytes_per_app {
+        for (app_index, (store_dir, usage)) in apps.iter_mut().enumerate() {
+            for candidate in candidates.iter_mut().filter(|c| c.app_index == app_index) {
+                if *usage <= max_bytes {
+                    break;
+                }
+                let removed = remove_gc_candidate(candidate, store_dir)?;
+                *usage = usage.saturating_sub(removed.bytes_freed);
+                result += removed;
+            }
+        }
+    }
+
+    if let Some(max_bytes) = policy.max_bytes_per_workspace {
+        let mut total: u64 = apps.iter().map(|(_, usage)| usage).sum();
+        for candidate in candidates.iter_mut().filter(|c| !c.removed) {
+            if total <= max_bytes {
+                break;
+            }
+            let (store_dir, usage) = &mut apps[candidate.app_index];
+            let removed = remove_gc_candidate(candidate, store_dir)?;
+            *usage = usage.saturating_sub(removed.bytes_freed);
+            total = total.saturating_sub(removed.bytes_freed);
+            result += removed;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Periodically apply the retention policy to the active workspace's checkpoints
+pub fn start_checkpoint_gc() {
+    std::thread::spawn(|| {
+        std::thread::sleep(GC_INITIAL_DELAY);
+        loop {
+            if let Err(e) = collect_checkpoint_garbage() {
+                warn!("Checkpoint garbage collection failed: {}", e);
+            }
+            std::thread::sleep(GC_INTERVAL);
+        }
+    });
+}
+
+// ============================================================================
+// INTEGRITY
+// ============================================================================
+
+/// What `verify_checkpoints` found wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegrityIssueKind {
+    /// The manifest can't be read, so none of its snapshots can be checked
+    CorruptManifest,
+    /// Listed in the manifest, but the snapshot file doesn't exist
+    MissingSnapshot,
+    /// The snapshot file exists but can't be parsed
+    CorruptSnapshot,
+    /// A snapshot file the manifest doesn't list
+    OrphanedSnapshot,
+    /// Referenced by a snapshot, but not in the blob store
+    MissingBlob,
+    /// Fails to decode, or its content doesn't match its hash
+    CorruptBlob,
+    /// Stored, but no snapshot references it
+    OrphanedBlob,
+    /// `refs.json` disagrees with the snapshots on disk
+    RefCountMismatch,
+    /// A git-backed snapshot's commit is not in the app's repository
+    MissingCommit,
+}
+
+/// A single problem found by `verify_checkpoints`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    pub kind: IntegrityIssueKind,
+    pub app_id: String,
+    pub conversation_id: Option<String>,
+    /// Snapshot ID, or the file name for orphaned snapshot files that can't be parsed
+    pub snapshot_id: Option<String>,
+    pub message_id: Option<String>,
+    pub hash: Option<String>,
+    pub detail: String,
+}
+
+/// Result of verifying (and optionally repairing) checkpoints
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResult {
+    pub apps_checked: usize,
+    pub snapshots_checked: usize,
+    pub blobs_checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+    /// What repair removed: unrecoverable snapshots dropped from their
+    /// manifests, and corrupt or orphaned blobs deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repair: Option<CleanupResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BlobHealth {
+    Ok,
+    Missing,
+    Corrupt(String),
+}
+
+fn check_blob_at_path(store_dir: &Path, hash: &str) -> BlobHealth {
+    match blob_path_at_path(store_dir, hash) {
+        Ok(path) if path.exists() => {}
+        _ => return BlobHealth::Missing,
+    }
+
+    match read_blob_at_path(store_dir, hash) {
+        Ok(content) if hash_content(&content) == hash => BlobHealth::Ok,
+        Ok(_) => BlobHealth::Corrupt("Content does not match its hash".to_string()),
+        Err(e) => BlobHealth::Corrupt(e),
+    }
+}
+
+/// Hashes and paths of every blob file in a store
+fn stored_blobs_at_path(store_dir: &Path) -> Vec<(String, PathBuf)> {
+    WalkDir::new(store_dir.join("blobs"))
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let hash = entry.file_name().to_str()?.to_string();
+            is_valid_blob_hash(&hash).then(|| (hash, entry.into_path()))
+        })
+        .collect()
+}
+
+/// Keys of the snapshot files in a conversation directory (see
+/// `CheckpointSummary::file_key`)
+fn snapshot_file_keys(conversation_dir: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(conversation_dir.join("snapshots")) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .collect()
+}
+
+/// Snapshot files whose key isn't in `listed`, as (snapshot ID, message ID).
+/// Files that can't be parsed are reported by file key, without a message ID.
+fn orphaned_snapshots_at_path(
+    conversation_dir: &Path,
+    listed: &HashSet<&str>,
+) -> Vec<(String, Option<String>)> {
+    snapshot_file_keys(conversation_dir)
+        .into_iter()
+        .filter(|file_key| !listed.contains(file_key.as_str()))
+        .map(|file_key| match load_snapshot_at_path(conversation_dir, &file_key) {
+            Ok(snapshot) => (snapshot.id, Some(snapshot.message_id)),
+            Err(_) => (file_key, None),
+        })
+        .collect()
+}
 
-    if !app_dir_path.exists() {
-        return Err(format!("App directory does not exist: {}", app_dir));
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Verify one app's manifests, snapshots and blob store.
+///
+/// With `repair`, snapshots that can't be fully restored are dropped from
+/// their manifest, corrupt and unreferenced blobs are deleted, and
+/// `refs.json` is rebuilt. Orphaned snapshot files are only reported: their
+/// blobs stay referenced so nothing they point to is lost.
+fn verify_app_at_path(
+    app_checkpoints_dir: &Path,
+    repair: bool,
+    result: &mut VerifyResult,
+) -> Result<CleanupResult, String> {
+    let _lock = acquire_checkpoint_lock_at_path(app_checkpoints_dir)?;
+    let app_id = dir_name(app_checkpoints_dir);
+    let store_dir = ensure_blob_store_at_path(app_checkpoints_dir)?;
+    let mut repaired = CleanupResult::default();
+    let mut blob_health: BTreeMap<String, BlobHealth> = BTreeMap::new();
+
+    // The snapshot an issue concerns, as (snapshot ID, message ID)
+    type SnapshotIds<'a> = (Option<&'a str>, Option<&'a str>);
+    let mut report = |kind: IntegrityIssueKind,
+                      conversation_id: Option<&str>,
+                      (snapshot_id, message_id): SnapshotIds,
+                      hash: Option<&str>,
+                      detail: String| {
+        result.issues.push(IntegrityIssue {
+            kind,
+            app_id: app_id.clone(),
+            conversation_id: conversation_id.map(str::to_string),
+            snapshot_id: snapshot_id.map(str::to_string),
+            message_id: message_id.map(str::to_string),
+            hash: hash.map(str::to_string),
+            detail,
+        });
+    };
+
+    // Reference counts as they should be, from every snapshot file on disk
+    let expected_refs = rebuild_blob_refs_at_path(app_checkpoints_dir)?;
+    let stored_refs = load_blob_refs_at_path(&store_dir)
+        .map(|refs| refs.refs)
+        .unwrap_or_default();
+    if stored_refs != expected_refs.refs {
+        report(
+            IntegrityIssueKind::RefCountMismatch,
+            None,
+            (None, None),
+            None,
+            format!(
+                "{} blobs tracked, {} referenced by snapshots",
+                stored_refs.len(),
+                expected_refs.refs.len()
+            ),
+        );
     }
 
-    // Scan all source files in the app directory
-    let source_files = scan_source_files(&app_dir_path)?;
-    
-    let mut files: Vec<FileEntry> = vec![];
-    let mut total_bytes: u64 = 0;
-    let mut blobs_created: usize = 0;
-    let mut blobs_reused: usize = 0;
+    let mut snapshots_checked = 0;
+    for conversation_dir in checkpoint_subdirs(app_checkpoints_dir)? {
+        let conversation_id = dir_name(&conversation_dir);
+        let conv = Some(conversation_id.as_str());
+
+        let mut manifest = match load_manifest_at_path(&conversation_dir) {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => {
+                for (snapshot_id, message_id) in
+                    orphaned_snapshots_at_path(&conversation_dir, &HashSet::new())
+                {
+                    report(
+                        IntegrityIssueKind::OrphanedSnapshot,
+                        conv,
+                        (Some(&snapshot_id), message_id.as_deref()),
+                        None,
+                        "Conversation has no manifest".to_string(),
+                    );
+                }
+                continue;
+            }
+            Err(e) => {
+                report(IntegrityIssueKind::CorruptManifest, conv, (None, None), None, e);
+                continue;
+            }
+        };
 
-    for relative_path in &source_files {
-        let full_path = validate_path_in_app_dir(&app_dir_path, relative_path)?;
+        let mut unrecoverable = HashSet::new();
+        for summary in &manifest.snapshots {
+            snapshots_checked += 1;
+            let ids = (Some(summary.id.as_str()), Some(summary.message_id.as_str()));
+            let file_key = summary.file_key();
+
+            let snapshot = match load_snapshot_at_path(&conversation_dir, file_key) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    let kind = if snapshot_path_at_path(&conversation_dir, file_key).exists() {
+                        IntegrityIssueKind::CorruptSnapshot
+                    } else {
+                        IntegrityIssueKind::MissingSnapshot
+                    };
+                    report(kind, conv, ids, None, e);
+                    unrecoverable.insert(file_key.to_string());
+                    continue;
+                }
+            };
+
+            if let Some(commit) = &snapshot.git_commit {
+                let app_dir = Path::new(&snapshot.app_dir);
+                if !GitRepo::discover(app_dir).is_some_and(|repo| repo.has_commit(commit)) {
+                    let detail = format!("Commit not found in {}", snapshot.app_dir);
+                    report(IntegrityIssueKind::MissingCommit, conv, ids, Some(commit), detail);
+                    unrecoverable.insert(file_key.to_string());
+                }
+            }
 
-        if !full_path.exists() {
-            continue;
+            for hash in snapshot_blob_hashes(&snapshot) {
+                let health = blob_health
+                    .entry(hash.clone())
+                    .or_insert_with(|| check_blob_at_path(&store_dir, &hash));
+                let (kind, detail) = match health {
+                    BlobHealth::Ok => continue,
+                    BlobHealth::Missing => {
+                        (IntegrityIssueKind::MissingBlob, "Blob not found".to_string())
+                    }
+                    BlobHealth::Corrupt(detail) => {
+                        (IntegrityIssueKind::CorruptBlob, detail.clone())
+                    }
+                };
+                report(kind, conv, ids, Some(&hash), detail);
+                unrecoverable.insert(file_key.to_string());
+            }
         }
 
-        let metadata = full_path
-            .metadata()
-            .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-        let size = metadata.len();
+        let listed: HashSet<&str> = manifest.snapshots.iter().map(|s| s.file_key()).collect();
+        for (snapshot_id, message_id) in orphaned_snapshots_at_path(&conversation_dir, &listed) {
+            report(
+                IntegrityIssueKind::OrphanedSnapshot,
+                conv,
+                (Some(&snapshot_id), message_id.as_deref()),
+                None,
+                "Not listed in the manifest".to_string(),
+            );
+        }
 
-        // Skip files that are too large (10MB limit per file)
-        if size > MAX_FILE_BYTES {
+        if repair && !unrecoverable.is_empty() {
+            let removed = remove_snapshots_at_path(
+                &conversation_dir,
+                &store_dir,
+                &mut manifest,
+                &unrecoverable,
+            )?;
+            repaired.snapshots_deleted += unrecoverable.len();
+            repaired.blobs_deleted += removed.blobs_deleted;
+            repaired.bytes_freed += removed.bytes_freed;
+        }
+    }
+
+    let stored_blobs = stored_blobs_at_path(&store_dir);
+    for (hash, _) in &stored_blobs {
+        if !expected_refs.refs.contains_key(hash) {
+            report(
+                IntegrityIssueKind::OrphanedBlob,
+                None,
+                (None, None),
+                Some(hash),
+                "No snapshot references this blob".to_string(),
+            );
             continue;
         }
+        // Blobs only orphaned snapshot files point to haven't been checked yet
+        if !blob_health.contains_key(hash) {
+            let health = check_blob_at_path(&store_dir, hash);
+            if let BlobHealth::Corrupt(detail) = &health {
+                let detail = detail.clone();
+                report(IntegrityIssueKind::CorruptBlob, None, (None, None), Some(hash), detail);
+            }
+            blob_health.insert(hash.clone(), health);
+        }
+    }
 
-        // Enforce total size limit (100MB per checkpoint)
-        if total_bytes + size > MAX_CHECKPOINT_BYTES {
-            break;
+    result.apps_checked += 1;
+    result.snapshots_checked += snapshots_checked;
+    result.blobs_checked += stored_blobs.len();
+
+    if repair {
+        // Dropped snapshots may have orphaned more blobs, so recount first
+        let refs = rebuild_blob_refs_at_path(app_checkpoints_dir)?;
+        for (hash, path) in stored_blobs_at_path(&store_dir) {
+            let corrupt = matches!(blob_health.get(&hash), Some(BlobHealth::Corrupt(_)));
+            if refs.refs.contains_key(&hash) && !corrupt {
+                continue;
+            }
+            if let Ok(metadata) = path.metadata() {
+                if fs::remove_file(&path).is_ok() {
+                    repaired.blobs_deleted += 1;
+                    repaired.bytes_freed += metadata.len();
+                }
+            }
         }
-        total_bytes += size;
+        save_blob_refs_at_path(&store_dir, &refs)?;
+    }
 
-        let content = fs::read(&full_path).map_err(|e| format!("Failed to read file: {}", e))?;
-        let (hash, was_created) = store_blob(&app_id, &conversation_id, &content)?;
+    Ok(repaired)
+}
 
-        if was_created {
-            blobs_created += 1;
-        } else {
-            blobs_reused += 1;
+/// Verify every app under `checkpoints_dir`, or just `app_id`
+fn verify_checkpoints_at_path(
+    checkpoints_dir: &Path,
+    app_id: Option<&str>,
+    repair: bool,
+) -> Result<VerifyResult, String> {
+    let app_dirs = match app_id {
+        Some(app_id) => {
+            let app_checkpoints_dir = checkpoints_dir.join(app_id);
+            if app_checkpoints_dir.is_dir() {
+                vec![app_checkpoints_dir]
+            } else {
+                vec![]
+            }
         }
+        None => checkpoint_subdirs(checkpoints_dir)?,
+    };
 
-        // Get file mode (Unix permissions)
-        #[cfg(unix)]
-        let mode = {
-            use std::os::unix::fs::MetadataExt;
-            metadata.mode()
-        };
-        #[cfg(not(unix))]
-        let mode = 0o644u32;
+    let mut result = VerifyResult::default();
+    let mut repaired = CleanupResult::default();
+    for app_checkpoints_dir in app_dirs {
+        repaired += verify_app_at_path(&app_checkpoints_dir, repair, &mut result)?;
+    }
 
-        files.push(FileEntry {
-            path: relative_path.clone(),
-            hash: Some(hash),
-            size,
-            mode,
-            exists: true,
-        });
+    if repair {
+        result.repair = Some(repaired);
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// TAURI COMMANDS
+// ============================================================================
+
+/// Create a checkpoint by scanning all source files in the app directory.
+/// This captures the complete state of the app before an AI response.
+///
+/// A message can have several checkpoints, e.g. one before each tool call;
+/// `label` tells them apart. Each gets its own snapshot ID.
+#[tauri::command]
+pub async fn create_checkpoint(
+    app_id: String,
+    app_dir: String,
+    conversation_id: String,
+    message_id: String,
+    full_hash: Option<bool>,
+    label: Option<String>,
+) -> Result<CheckpointResult, String> {
+    let app_checkpoints_dir = get_app_checkpoints_dir(&app_id)?;
+    let request = CaptureRequest {
+        app_id: &app_id,
+        app_dir: &app_dir,
+        conversation_id: &conversation_id,
+        message_id: &message_id,
+        kind: SnapshotKind::Message,
+        label,
+        restores: None,
+        full_hash: full_hash.unwrap_or(false),
+    };
+    let (_, result) = capture_snapshot(&app_checkpoints_dir, &request)?;
+    Ok(result)
+}
+
+/// What `capture_snapshot` should capture, and where it goes
+struct CaptureRequest<'a> {
+    app_id: &'a str,
+    app_dir: &'a str,
+    conversation_id: &'a str,
+    message_id: &'a str,
+    kind: SnapshotKind,
+    label: Option<String>,
+    /// For pre-restore snapshots, the snapshot about to be restored
+    restores: Option<&'a Snapshot>,
+    full_hash: bool,
+}
+
+/// Take the app's checkpoint lock and capture a snapshot
+fn capture_snapshot(
+    app_checkpoints_dir: &Path,
+    request: &CaptureRequest,
+) -> Result<(Snapshot, CheckpointResult), String> {
+    let _lock = acquire_checkpoint_lock_at_path(app_checkpoints_dir)?;
+    capture_snapshot_locked(app_checkpoints_dir, request)
+}
+
+/// Snapshot every source file in `app_dir` into the blob store (or the app's
+/// git repository, see `git_checkpoint_repo`) and record it in the
+/// conversation's manifest. Unchanged files are picked up from the stat cache
+/// unless `full_hash` is set. The caller must hold the checkpoint lock.
+fn capture_snapshot_locked(
+    app_checkpoints_dir: &Path,
+    request: &CaptureRequest,
+) -> Result<(Snapshot, CheckpointResult), String> {
+    let CaptureRequest {
+        app_id,
+        app_dir,
+        conversation_id,
+        message_id,
+        kind,
+        ..
+    } = *request;
+    let app_dir_path = PathBuf::from(app_dir);
+
+    if !app_dir_path.exists() {
+        return Err(format!("App directory does not exist: {}", app_dir));
+    }
+
+    let git_repo = git_checkpoint_repo(&app_dir_path, conversation_id);
+    let WorkingTreeScan {
+        mut files,
+        total_bytes,
+        blobs_created,
+        blobs_reused,
+        ..
+    } = scan_working_tree_cached_at_path(
+        app_checkpoints_dir,
+        &app_dir_path,
+        git_repo.is_none(),
+        request.full_hash,
+    )?;
+    if let Some(repo) = &git_repo {
+        repo.hash_files(&mut files, true)?;
     }
 
-    let snapshot_id = format!("snap-{}", &message_id[..8.min(message_id.len())]);
+    let conversation_dir = app_checkpoints_dir.join(conversation_id);
+    let store_dir = ensure_blob_store_at_path(app_checkpoints_dir)?;
+    let mut manifest =
+        load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id)?;
 
-    let snapshot = Snapshot {
+    let created_at = Utc::now();
+    let snapshot_id = new_snapshot_id(&conversation_dir, &manifest, message_id, created_at);
+
+    let mut snapshot = Snapshot {
         id: snapshot_id.clone(),
-        message_id: message_id.clone(),
-        conversation_id: conversation_id.clone(),
-        app_id: app_id.clone(),
-        app_dir: app_dir.clone(),
-        created_at: Utc::now(),
-        files: files.clone(),
+        message_id: message_id.to_string(),
+        conversation_id: conversation_id.to_string(),
+        app_id: app_id.to_string(),
+        app_dir: app_dir.to_string(),
+        created_at,
+        files,
+        kind,
+        restored_message_id: request.restores.map(|target| target.message_id.clone()),
+        restored_snapshot_id: request.restores.map(|target| target.id.clone()),
+        label: request.label.clone(),
+        git_commit: None,
     };
+    if let Some(repo) = &git_repo {
+        snapshot.git_commit = Some(repo.commit_snapshot(&snapshot)?);
+    }
 
-    save_snapshot(&snapshot)?;
+    save_snapshot_at_path(&conversation_dir, &snapshot)?;
+    retain_blobs_at_path(&store_dir, &snapshot_blob_hashes(&snapshot))?;
 
     // Update manifest
-    let mut manifest = load_or_create_manifest(&app_id, &conversation_id)?;
     manifest.updated_at = Utc::now();
     manifest.snapshots.push(CheckpointSummary {
         id: snapshot_id.clone(),
-        message_id: message_id.clone(),
-        created_at: snapshot.created_at,
-        file_count: files.len(),
+        message_id: message_id.to_string(),
+        created_at,
+        file_count: snapshot.files.len(),
         total_bytes,
         has_changes: false, // Computed when listing
+        kind,
+        label: request.label.clone(),
+        layout: SnapshotLayout::ById,
     });
-    save_manifest(&manifest)?;
+    save_manifest_at_path(&conversation_dir, &manifest)?;
 
-    Ok(CheckpointResult {
+    let result = CheckpointResult {
         id: snapshot_id,
-        message_id,
-        file_count: files.len(),
+        message_id: message_id.to_string(),
+        file_count: snapshot.files.len(),
         total_bytes,
         blobs_created,
         blobs_reused,
-    })
-}
-
-fn hash_file_if_allowed(path: &Path) -> Option<String> {
-    let metadata = path.metadata().ok()?;
-    if metadata.len() > MAX_FILE_BYTES {
-        return None;
-    }
-    let content = std::fs::read(path).ok()?;
-    Some(hash_content(&content))
+    };
+    Ok((snapshot, result))
 }
 
 /// List checkpoints for a conversation within an app.
@@ -574,10 +2550,13 @@ fn hash_file_if_allowed(path: &Path) -> Option<String> {
 /// This means: "did the AI make changes AFTER this checkpoint was created?"
 /// - If checkpoint A differs from checkpoint B, then A shows an undo button
 /// - For the LAST checkpoint, compare with current disk state
+///
+/// The disk state is hashed through the stat cache unless `full_hash` is set.
 #[tauri::command]
 pub fn list_checkpoints(
     app_id: String,
     conversation_id: String,
+    full_hash: Option<bool>,
 ) -> Result<Vec<CheckpointSummary>, String> {
     let manifest_path = get_manifest_path(&app_id, &conversation_id)?;
 
@@ -585,62 +2564,62 @@ pub fn list_checkpoints(
         return Ok(vec![]);
     }
 
-    let manifest = load_or_create_manifest(&app_id, &conversation_id)?;
-    
-    if manifest.snapshots.is_empty() {
+    let conversation_dir = get_checkpoint_dir(&app_id, &conversation_id)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, &app_id, &conversation_id)?;
+
+    // Pre-restore safety snapshots aren't tied to a message, so they aren't listed.
+    // A checkpoint whose snapshot can't be read can't be restored either, so it
+    // is skipped instead of being compared as if it had no files.
+    let mut snapshots: Vec<(CheckpointSummary, Snapshot)> = Vec::new();
+    for summary in manifest.snapshots {
+        if summary.kind != SnapshotKind::Message {
+            continue;
+        }
+        match load_snapshot_at_path(&conversation_dir, summary.file_key()) {
+            Ok(snapshot) => snapshots.push((summary, snapshot)),
+            Err(e) => warn!(
+                "Skipping checkpoint {} (run verify_checkpoints to repair): {}",
+                summary.id, e
+            ),
+        }
+    }
+
+    if snapshots.is_empty() {
         return Ok(vec![]);
     }
-    
-    // First, load all snapshot hashes
-    let snapshot_hashes: Vec<HashSet<(String, Option<String>)>> = manifest.snapshots
+
+    let snapshot_hashes: Vec<HashSet<(String, Option<String>)>> = snapshots
         .iter()
-        .map(|summary| {
-            if let Ok(snapshot) = load_snapshot(&app_id, &conversation_id, &summary.message_id) {
-                snapshot.files.iter()
-                    .map(|f| (f.path.clone(), f.hash.clone()))
-                    .collect()
-            } else {
-                HashSet::new()
-            }
+        .map(|(_, snapshot)| {
+            snapshot
+                .files
+                .iter()
+                .map(|f| (f.path.clone(), f.hash.clone()))
+                .collect()
         })
         .collect();
-    
+
     // For the last checkpoint, get current disk state to compare against
     let current_disk_hashes: HashSet<(String, Option<String>)> = {
-        // Get app_dir from the last snapshot
-        let last_snapshot = load_snapshot(
-            &app_id, 
-            &conversation_id, 
-            &manifest.snapshots.last().unwrap().message_id
-        );
-        
-        if let Ok(snapshot) = last_snapshot {
-            let app_dir = PathBuf::from(&snapshot.app_dir);
-            if app_dir.exists() {
-                // Scan current files and compute their hashes
-                // scan_source_files returns relative paths already
-                scan_source_files(&app_dir)
-                    .unwrap_or_default()
-                    .iter()
-                    .filter_map(|rel_path| {
-                        let full_path = app_dir.join(rel_path);
-                        let hash = hash_file_if_allowed(&full_path)?;
-                        Some((rel_path.clone(), Some(hash)))
-                    })
-                    .collect()
-            } else {
-                HashSet::new()
-            }
+        let (_, last_snapshot) = snapshots.last().unwrap();
+        let app_dir = PathBuf::from(&last_snapshot.app_dir);
+        if app_dir.exists() {
+            // Scan current files and compute their hashes
+            scan_working_tree_like(&app_id, &app_dir, last_snapshot, full_hash.unwrap_or(false))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|f| (f.path, f.hash))
+                .collect()
         } else {
             HashSet::new()
         }
     };
-    
+
     // Compare each checkpoint with the NEXT one (or current disk for last)
     let mut result: Vec<CheckpointSummary> = Vec::new();
-    let last_idx = manifest.snapshots.len() - 1;
-    
-    for (i, summary) in manifest.snapshots.into_iter().enumerate() {
+    let last_idx = snapshots.len() - 1;
+
+    for (i, (summary, _)) in snapshots.into_iter().enumerate() {
         let has_changes = if i < last_idx {
             // Compare with next checkpoint
             snapshot_hashes[i] != snapshot_hashes[i + 1]
@@ -648,20 +2627,84 @@ pub fn list_checkpoints(
             // Last checkpoint: compare with current disk state
             snapshot_hashes[i] != current_disk_hashes
         };
-        
+
         result.push(CheckpointSummary {
-            id: summary.id,
-            message_id: summary.message_id,
-            created_at: summary.created_at,
-            file_count: summary.file_count,
-            total_bytes: summary.total_bytes,
             has_changes,
+            ..summary
         });
     }
-    
+
     Ok(result)
 }
 
+/// Diff a checkpoint against a later checkpoint or the current working tree.
+///
+/// Each side is picked by snapshot ID, or else by message ID (its latest
+/// snapshot). Without a target, the checkpoint is compared with the files
+/// currently on disk in the snapshot's app directory.
+#[tauri::command]
+pub fn diff_checkpoints(
+    app_id: String,
+    conversation_id: String,
+    from_message_id: Option<String>,
+    to_message_id: Option<String>,
+    from_snapshot_id: Option<String>,
+    to_snapshot_id: Option<String>,
+) -> Result<CheckpointDiff, String> {
+    let conversation_dir = get_checkpoint_dir(&app_id, &conversation_id)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, &app_id, &conversation_id)?;
+    let load = |snapshot_id: Option<&str>, message_id: Option<&str>| {
+        let index = find_snapshot_index(&manifest, snapshot_id, message_id)?;
+        load_snapshot_at_path(&conversation_dir, manifest.snapshots[index].file_key())
+    };
+
+    let from_snapshot = load(from_snapshot_id.as_deref(), from_message_id.as_deref())?;
+    let to_snapshot = match (&to_snapshot_id, &to_message_id) {
+        (None, None) => None,
+        _ => Some(load(to_snapshot_id.as_deref(), to_message_id.as_deref())?),
+    };
+    let app_checkpoints_dir = get_app_checkpoints_dir(&app_id)?;
+    let from_files = file_version_map(&from_snapshot.files);
+    let from_source = ContentSource::for_snapshot(&app_checkpoints_dir, &from_snapshot)?;
+    let read_from = |_: &str, hash: &str| from_source.read(hash);
+
+    let files = match &to_snapshot {
+        Some(to_snapshot) => {
+            let to_files = file_version_map(&to_snapshot.files);
+            let to_source = ContentSource::for_snapshot(&app_checkpoints_dir, to_snapshot)?;
+            diff_file_maps(&from_files, &to_files, read_from, |_, hash| to_source.read(hash))?
+        }
+        None => {
+            let app_dir = PathBuf::from(&from_snapshot.app_dir);
+            if !app_dir.exists() {
+                return Err(format!(
+                    "App directory no longer exists: {}",
+                    from_snapshot.app_dir
+                ));
+            }
+            let scan = scan_working_tree_like(&app_id, &app_dir, &from_snapshot, false)?;
+            let to_files = file_version_map(&scan);
+            diff_file_maps(&from_files, &to_files, read_from, |path, _| {
+                let full_path = validate_path_in_app_dir(&app_dir, path)?;
+                fs::read(&full_path).map_err(|e| format!("Failed to read file: {}", e))
+            })?
+        }
+    };
+
+    let count = |kind: FileChangeKind| files.iter().filter(|f| f.change == kind).count();
+
+    Ok(CheckpointDiff {
+        from_snapshot_id: from_snapshot.id,
+        from_message_id: from_snapshot.message_id,
+        to_snapshot_id: to_snapshot.as_ref().map(|snapshot| snapshot.id.clone()),
+        to_message_id: to_snapshot.map(|snapshot| snapshot.message_id),
+        added: count(FileChangeKind::Added),
+        modified: count(FileChangeKind::Modified),
+        deleted: count(FileChangeKind::Deleted),
+        files,
+    })
+}
+
 /// Restore files to a checkpoint state.
 /// 
 /// This restores ALL files to their state at the target checkpoint, including:
@@ -670,22 +2713,286 @@ pub fn list_checkpoints(
 ///   didn't exist or had different content at checkpoint time)
 /// 
 /// This allows users to "undo" all changes from a certain point forward in the
-/// conversation.
+/// conversation. The current working tree is captured first as a pre-restore
+/// snapshot, so the restore itself can be undone with `redo_restore`.
+///
+/// The target is `snapshot_id` if given, otherwise the latest snapshot
+/// captured for `message_id`.
 #[tauri::command]
 pub async fn restore_checkpoint(
     app_id: String,
     conversation_id: String,
-    message_id: String,
+    message_id: Option<String>,
+    snapshot_id: Option<String>,
+) -> Result<RestoreResult, String> {
+    let _lock = acquire_checkpoint_lock(&app_id)?;
+    let (target_snapshot, later_paths) = load_restore_target(
+        &app_id,
+        &conversation_id,
+        snapshot_id.as_deref(),
+        message_id.as_deref(),
+    )?;
+    let app_dir_path = existing_app_dir(&target_snapshot)?;
+    let source = ContentSource::for_snapshot(&get_app_checkpoints_dir(&app_id)?, &target_snapshot)?;
+    let pre_restore = capture_pre_restore_snapshot(&target_snapshot)?;
+
+    let mut result =
+        restore_snapshot_files(&app_dir_path, &target_snapshot, &later_paths, None, |hash| {
+            source.read(hash)
+        })?;
+    result.pre_restore_message_id = Some(pre_restore.message_id);
+    result.pre_restore_snapshot_id = Some(pre_restore.id);
+    Ok(result)
+}
+
+/// Restore only the selected paths to their state at a checkpoint.
+///
+/// Each entry in `paths` is either a relative path (a file, or a directory
+/// whose contents are all selected) or a glob pattern such as
+/// `src/components/**/*.tsx`. Files outside the selection are left untouched.
+/// Selected files created after the checkpoint are deleted, same as a full
+/// restore.
+#[tauri::command]
+pub async fn restore_checkpoint_paths(
+    app_id: String,
+    conversation_id: String,
+    message_id: Option<String>,
+    paths: Vec<String>,
+    snapshot_id: Option<String>,
+) -> Result<RestoreResult, String> {
+    let _lock = acquire_checkpoint_lock(&app_id)?;
+    let (target_snapshot, later_paths) = load_restore_target(
+        &app_id,
+        &conversation_id,
+        snapshot_id.as_deref(),
+        message_id.as_deref(),
+    )?;
+    let app_dir_path = existing_app_dir(&target_snapshot)?;
+    let selection = PathSelection::parse(&app_dir_path, &paths)?;
+    let source = ContentSource::for_snapshot(&get_app_checkpoints_dir(&app_id)?, &target_snapshot)?;
+    let pre_restore = capture_pre_restore_snapshot(&target_snapshot)?;
+
+    let mut result = restore_snapshot_files(
+        &app_dir_path,
+        &target_snapshot,
+        &later_paths,
+        Some(&selection),
+        |hash| source.read(hash),
+    )?;
+    result.pre_restore_message_id = Some(pre_restore.message_id);
+    result.pre_restore_snapshot_id = Some(pre_restore.id);
+    Ok(result)
+}
+
+/// Undo a restore by re-applying the working tree captured just before it.
+///
+/// The pre-restore snapshot is picked by `pre_restore_snapshot_id` or
+/// `pre_restore_message_id`; with neither, the most recent one in the
+/// conversation is used. Files the undone restore brought back that didn't
+/// exist beforehand are deleted again.
+#[tauri::command]
+pub async fn redo_restore(
+    app_id: String,
+    conversation_id: String,
+    pre_restore_message_id: Option<String>,
+    pre_restore_snapshot_id: Option<String>,
 ) -> Result<RestoreResult, String> {
-    let target_snapshot = load_snapshot(&app_id, &conversation_id, &message_id)?;
-    let app_dir_path = PathBuf::from(&target_snapshot.app_dir);
+    let _lock = acquire_checkpoint_lock(&app_id)?;
+    let conversation_dir = get_checkpoint_dir(&app_id, &conversation_id)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, &app_id, &conversation_id)?;
+
+    let index = match (&pre_restore_snapshot_id, &pre_restore_message_id) {
+        (None, None) => manifest
+            .snapshots
+            .iter()
+            .rposition(|summary| summary.kind == SnapshotKind::PreRestore)
+            .ok_or_else(|| "No restore to undo for this conversation".to_string())?,
+        _ => find_snapshot_index(
+            &manifest,
+            pre_restore_snapshot_id.as_deref(),
+            pre_restore_message_id.as_deref(),
+        )?,
+    };
+
+    let summary = &manifest.snapshots[index];
+    if summary.kind != SnapshotKind::PreRestore {
+        return Err(format!("Snapshot {} is not a pre-restore snapshot", summary.id));
+    }
+    let pre_restore = load_snapshot_at_path(&conversation_dir, summary.file_key())?;
+    let app_dir_path = existing_app_dir(&pre_restore)?;
+
+    // The restore could only have written files from the snapshot it restored
+    let restored_index = find_snapshot_index(
+        &manifest,
+        pre_restore.restored_snapshot_id.as_deref(),
+        pre_restore.restored_message_id.as_deref(),
+    );
+    let restored = restored_index.and_then(|index| {
+        load_snapshot_at_path(&conversation_dir, manifest.snapshots[index].file_key())
+    });
+    let restored_paths: HashSet<String> = restored
+        .map(|snapshot| snapshot.files.into_iter().map(|f| f.path).collect())
+        .unwrap_or_default();
+
+    let source = ContentSource::for_snapshot(&get_app_checkpoints_dir(&app_id)?, &pre_restore)?;
+    restore_snapshot_files(&app_dir_path, &pre_restore, &restored_paths, None, |hash| {
+        source.read(hash)
+    })
+}
+
+/// Capture the current working tree before restoring `target_snapshot`.
+/// The caller must hold the checkpoint lock.
+fn capture_pre_restore_snapshot(target_snapshot: &Snapshot) -> Result<Snapshot, String> {
+    let message_id = format!("pre-restore-{}", Utc::now().timestamp_millis());
+    let app_checkpoints_dir = get_app_checkpoints_dir(&target_snapshot.app_id)?;
+    let request = CaptureRequest {
+        app_id: &target_snapshot.app_id,
+        app_dir: &target_snapshot.app_dir,
+        conversation_id: &target_snapshot.conversation_id,
+        message_id: &message_id,
+        kind: SnapshotKind::PreRestore,
+        label: None,
+        restores: Some(target_snapshot),
+        full_hash: false,
+    };
+    let (snapshot, _) = capture_snapshot_locked(&app_checkpoints_dir, &request)
+        .map_err(|e| format!("Failed to capture pre-restore snapshot: {}", e))?;
+    Ok(snapshot)
+}
+
+/// Load the snapshot a restore targets, along with every path touched by
+/// the checkpoints after it
+fn load_restore_target(
+    app_id: &str,
+    conversation_id: &str,
+    snapshot_id: Option<&str>,
+    message_id: Option<&str>,
+) -> Result<(Snapshot, HashSet<String>), String> {
+    let conversation_dir = get_checkpoint_dir(app_id, conversation_id)?;
+    let manifest = load_or_create_manifest_at_path(&conversation_dir, app_id, conversation_id)?;
+    let index = find_snapshot_index(&manifest, snapshot_id, message_id)?;
+    let target_snapshot =
+        load_snapshot_at_path(&conversation_dir, manifest.snapshots[index].file_key())?;
+    let later_paths = collect_later_checkpoint_paths(&conversation_dir, &manifest, index);
+    Ok((target_snapshot, later_paths))
+}
 
+/// Resolve a snapshot's app directory, failing if it has since been removed
+fn existing_app_dir(snapshot: &Snapshot) -> Result<PathBuf, String> {
+    let app_dir_path = PathBuf::from(&snapshot.app_dir);
     if !app_dir_path.exists() {
         return Err(format!(
             "App directory no longer exists: {}",
-            target_snapshot.app_dir
+            snapshot.app_dir
         ));
     }
+    Ok(app_dir_path)
+}
+
+/// Collect all paths touched by checkpoints AFTER the one at `target_index`.
+/// These files need to be either restored to target state or deleted.
+fn collect_later_checkpoint_paths(
+    conversation_dir: &Path,
+    manifest: &CheckpointManifest,
+    target_index: usize,
+) -> HashSet<String> {
+    let mut files_from_later_checkpoints: HashSet<String> = HashSet::new();
+
+    for summary in manifest.snapshots.iter().skip(target_index + 1) {
+        // Pre-restore snapshots hold the whole working tree, not AI changes
+        if summary.kind == SnapshotKind::PreRestore {
+            continue;
+        }
+
+        if let Ok(later_snapshot) = load_snapshot_at_path(conversation_dir, summary.file_key()) {
+            for file_entry in &later_snapshot.files {
+                files_from_later_checkpoints.insert(file_entry.path.clone());
+            }
+        }
+    }
+
+    files_from_later_checkpoints
+}
+
+/// A set of relative paths and glob patterns limiting which files a restore touches
+#[derive(Debug)]
+struct PathSelection {
+    /// Exact file paths or directory prefixes
+    literals: Vec<String>,
+    /// Glob patterns matched against the full relative path
+    patterns: Vec<glob::Pattern>,
+}
+
+impl PathSelection {
+    /// Parse user-supplied selection entries. Literal paths go through
+    /// `validate_path_in_app_dir`; patterns may not contain `..` or be absolute.
+    fn parse(app_dir: &Path, entries: &[String]) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("No paths selected for restore".to_string());
+        }
+
+        let mut literals = Vec::new();
+        let mut patterns = Vec::new();
+
+        for entry in entries {
+            let trimmed = entry.trim();
+            let trimmed = trimmed.strip_prefix("./").unwrap_or(trimmed);
+            let trimmed = trimmed.trim_end_matches('/');
+            if trimmed.is_empty() {
+                return Err(format!("Invalid restore path: {:?}", entry));
+            }
+
+            if trimmed.contains(['*', '?', '[']) {
+                let as_path = Path::new(trimmed);
+                if as_path.is_absolute()
+                    || as_path
+                        .components()
+                        .any(|c| matches!(c, Component::ParentDir))
+                {
+                    return Err(format!("Path contains invalid component: {}", trimmed));
+                }
+                let pattern = glob::Pattern::new(trimmed)
+                    .map_err(|e| format!("Invalid glob pattern {}: {}", trimmed, e))?;
+                patterns.push(pattern);
+            } else {
+                validate_path_in_app_dir(app_dir, trimmed)?;
+                literals.push(trimmed.to_string());
+            }
+        }
+
+        Ok(Self { literals, patterns })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        self.literals.iter().any(|literal| {
+            path == literal
+                || path
+                    .strip_prefix(literal.as_str())
+                    .is_some_and(|rest| rest.starts_with(['/', std::path::MAIN_SEPARATOR]))
+        }) || self
+            .patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(path, options))
+    }
+}
+
+/// Write the target snapshot's state into `app_dir`, deleting any `later_paths`
+/// that the snapshot doesn't know about. When `selection` is given only matching
+/// paths are touched and the counts reflect just those files.
+fn restore_snapshot_files(
+    app_dir_path: &Path,
+    target_snapshot: &Snapshot,
+    later_paths: &HashSet<String>,
+    selection: Option<&PathSelection>,
+    load_blob: impl Fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<RestoreResult, String> {
+    let selected = |path: &str| selection.is_none_or(|s| s.matches(path));
 
     // Build a set of files in the target snapshot for quick lookup
     let target_files: std::collections::HashMap<String, &FileEntry> = target_snapshot
@@ -694,27 +3001,11 @@ pub async fn restore_checkpoint(
         .map(|f| (f.path.clone(), f))
         .collect();
 
-    // Collect all files from checkpoints AFTER the target checkpoint
-    // These files need to be either restored to target state or deleted
-    let manifest = load_or_create_manifest(&app_id, &conversation_id)?;
-    let mut files_from_later_checkpoints: HashSet<String> = HashSet::new();
-    
-    // Find the target checkpoint index and collect files from later checkpoints
-    let mut found_target = false;
-    for summary in &manifest.snapshots {
-        if summary.message_id == message_id {
-            found_target = true;
-            continue; // Skip the target itself, start collecting from the next one
-        }
-        
-        if found_target {
-            // This checkpoint is after the target - collect its files
-            if let Ok(later_snapshot) = load_snapshot(&app_id, &conversation_id, &summary.message_id) {
-                for file_entry in &later_snapshot.files {
-                    files_from_later_checkpoints.insert(file_entry.path.clone());
-                }
-            }
-        }
+    if selection.is_some()
+        && !target_files.keys().any(|p| selected(p))
+        && !later_paths.iter().any(|p| selected(p))
+    {
+        return Err("No files in checkpoint match the selected paths".to_string());
     }
 
     let mut files_restored: usize = 0;
@@ -723,7 +3014,11 @@ pub async fn restore_checkpoint(
 
     // First, restore all files from the target snapshot
     for file_entry in &target_snapshot.files {
-        let full_path = validate_path_in_app_dir(&app_dir_path, &file_entry.path)?;
+        if !selected(&file_entry.path) {
+            continue;
+        }
+
+        let full_path = validate_path_in_app_dir(app_dir_path, &file_entry.path)?;
 
         if file_entry.exists {
             // Restore file content
@@ -732,11 +3027,11 @@ pub async fn restore_checkpoint(
                 .as_ref()
                 .ok_or_else(|| format!("Missing hash for file: {}", file_entry.path))?;
 
-            let content = read_blob(&app_id, &conversation_id, hash)?;
+            let content = load_blob(hash)?;
 
-            // Verify hash matches
+            // Verify hash matches (git checks its own objects)
             let actual_hash = hash_content(&content);
-            if &actual_hash != hash {
+            if target_snapshot.git_commit.is_none() && &actual_hash != hash {
                 return Err(format!(
                     "Hash mismatch for blob {}: expected {}, got {}",
                     file_entry.path, hash, actual_hash
@@ -776,14 +3071,14 @@ pub async fn restore_checkpoint(
 
     // Now handle files that were created/modified in LATER checkpoints but aren't
     // in the target snapshot (or exist with different state)
-    for later_file_path in &files_from_later_checkpoints {
+    for later_file_path in later_paths {
         // Skip if this file is already in the target snapshot (handled above)
-        if target_files.contains_key(later_file_path) {
+        if target_files.contains_key(later_file_path) || !selected(later_file_path) {
             continue;
         }
 
         // This file was created after the target checkpoint - delete it
-        let full_path = match validate_path_in_app_dir(&app_dir_path, later_file_path) {
+        let full_path = match validate_path_in_app_dir(app_dir_path, later_file_path) {
             Ok(p) => p,
             Err(_) => continue, // Skip invalid paths
         };
@@ -797,115 +3092,95 @@ pub async fn restore_checkpoint(
         files_restored,
         files_deleted,
         bytes_written,
+        pre_restore_message_id: None,
+        pre_restore_snapshot_id: None,
     })
 }
 
 /// Delete old checkpoints (garbage collection)
+///
+/// Blobs are shared with the app's other conversations, so a blob is only
+/// deleted once no remaining snapshot references it.
 #[tauri::command]
 pub fn cleanup_checkpoints(
     app_id: String,
     conversation_id: String,
     keep_last_n: usize,
 ) -> Result<CleanupResult, String> {
-    let mut manifest = load_or_create_manifest(&app_id, &conversation_id)?;
+    let app_checkpoints_dir = get_app_checkpoints_dir(&app_id)?;
+    let _lock = acquire_checkpoint_lock_at_path(&app_checkpoints_dir)?;
+    let conversation_dir = app_checkpoints_dir.join(&conversation_id);
+    let mut manifest =
+        load_or_create_manifest_at_path(&conversation_dir, &app_id, &conversation_id)?;
 
     if manifest.snapshots.len() <= keep_last_n {
-        return Ok(CleanupResult {
-            snapshots_deleted: 0,
-            blobs_deleted: 0,
-            bytes_freed: 0,
-        });
-    }
-
-    // Collect hashes from snapshots we're keeping
-    let snapshots_to_keep: Vec<_> = manifest.snapshots.iter().rev().take(keep_last_n).collect();
-    let mut hashes_to_keep = HashSet::new();
-
-    for summary in &snapshots_to_keep {
-        if let Ok(snapshot) = load_snapshot(&app_id, &conversation_id, &summary.message_id) {
-            for file in &snapshot.files {
-                if let Some(hash) = &file.hash {
-                    hashes_to_keep.insert(hash.clone());
-                }
-            }
-        }
+        return Ok(CleanupResult::default());
     }
 
-    // Delete old snapshots and collect their hashes
-    let snapshots_to_delete: Vec<_> = manifest
+    // Delete old snapshots and release their blob references
+    let store_dir = ensure_blob_store_at_path(&app_checkpoints_dir)?;
+    let file_keys: HashSet<String> = manifest
         .snapshots
         .iter()
         .rev()
         .skip(keep_last_n)
-        .cloned()
+        .map(|summary| summary.file_key().to_string())
         .collect();
-    let mut hashes_to_maybe_delete = HashSet::new();
-    let mut snapshots_deleted = 0;
-
-    for summary in &snapshots_to_delete {
-        if let Ok(snapshot) = load_snapshot(&app_id, &conversation_id, &summary.message_id) {
-            for file in &snapshot.files {
-                if let Some(hash) = &file.hash {
-                    hashes_to_maybe_delete.insert(hash.clone());
-                }
-            }
-        }
 
-        // Delete snapshot file
-        if let Ok(path) = get_snapshot_path(&app_id, &conversation_id, &summary.message_id) {
-            if path.exists() {
-                fs::remove_file(&path).ok();
-                snapshots_deleted += 1;
-            }
-        }
-    }
+    remove_snapshots_at_path(&conversation_dir, &store_dir, &mut manifest, &file_keys)
+}
 
-    // Delete blobs not referenced by kept snapshots
-    let hashes_to_delete: Vec<_> = hashes_to_maybe_delete
-        .difference(&hashes_to_keep)
-        .cloned()
-        .collect();
-    let mut blobs_deleted = 0;
-    let mut bytes_freed = 0u64;
+/// Apply the retention policy from shared preferences to every app and
+/// conversation in the active workspace
+#[tauri::command]
+pub fn collect_checkpoint_garbage() -> Result<CleanupResult, String> {
+    let checkpoints_dir = get_checkpoints_dir()?;
+    let policy = RetentionPolicy::load();
+    let result = collect_garbage_at_path(&checkpoints_dir, &policy, Utc::now())?;
 
-    for hash in &hashes_to_delete {
-        if let Ok(path) = get_blob_path(&app_id, &conversation_id, hash) {
-            if path.exists() {
-                if let Ok(metadata) = path.metadata() {
-                    bytes_freed += metadata.len();
-                }
-                if fs::remove_file(&path).is_ok() {
-                    blobs_deleted += 1;
-                }
-            }
-        }
+    if result.snapshots_deleted > 0 {
+        info!(
+            "Checkpoint GC deleted {} snapshots and {} blobs ({} bytes)",
+            result.snapshots_deleted, result.blobs_deleted, result.bytes_freed
+        );
     }
 
-    // Update manifest
-    manifest.snapshots = manifest
-        .snapshots
-        .into_iter()
-        .rev()
-        .take(keep_last_n)
-        .rev()
-        .collect();
-    manifest.updated_at = Utc::now();
-    save_manifest(&manifest)?;
+    Ok(result)
+}
 
-    Ok(CleanupResult {
-        snapshots_deleted,
-        blobs_deleted,
-        bytes_freed,
-    })
+/// Check an app's checkpoints (or every app's, if `app_id` is omitted) for
+/// missing, corrupt and orphaned snapshots and blobs. With `repair`, drops
+/// snapshots that can no longer be restored and deletes bad or unused blobs.
+#[tauri::command]
+pub fn verify_checkpoints(
+    app_id: Option<String>,
+    repair: Option<bool>,
+) -> Result<VerifyResult, String> {
+    let checkpoints_dir = get_checkpoints_dir()?;
+    let result =
+        verify_checkpoints_at_path(&checkpoints_dir, app_id.as_deref(), repair.unwrap_or(false))?;
+
+    if !result.issues.is_empty() {
+        warn!(
+            "Checkpoint verification found {} issues in {} apps",
+            result.issues.len(),
+            result.apps_checked
+        );
+    }
+
+    Ok(result)
 }
 
-/// Delete all checkpoints for an app (called when app is deleted)
+/// Delete all checkpoints for an app (called when app is deleted).
+/// The app's blob store goes with it, since only its snapshots can reference it.
 #[tauri::command]
 pub fn delete_app_checkpoints(app_id: String) -> Result<(), String> {
     let checkpoints_dir = get_checkpoints_dir()?;
     let app_checkpoints_dir = checkpoints_dir.join(&app_id);
 
     if app_checkpoints_dir.exists() {
+        // Wait out any in-flight capture; the lock file goes with the directory
+        let _lock = acquire_checkpoint_lock_at_path(&app_checkpoints_dir)?;
         fs::remove_dir_all(&app_checkpoints_dir)
             .map_err(|e| format!("Failed to delete app checkpoints: {}", e))?;
     }
@@ -976,6 +3251,7 @@ mod tests {
             size: 1024,
             mode: 0o644,
             exists: true,
+            binary: false,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -995,6 +3271,7 @@ mod tests {
             size: 0,
             mode: 0,
             exists: false,
+            binary: false,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -1019,7 +3296,13 @@ mod tests {
                 size: 100,
                 mode: 0o644,
                 exists: true,
+                binary: false,
             }],
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
         };
 
         let json = serde_json::to_string_pretty(&snapshot).unwrap();
@@ -1043,6 +3326,9 @@ mod tests {
                 file_count: 3,
                 total_bytes: 1024,
                 has_changes: false,
+                kind: SnapshotKind::Message,
+                label: None,
+                layout: SnapshotLayout::ById,
             }],
         };
 
@@ -1053,13 +3339,60 @@ mod tests {
         assert_eq!(parsed.snapshots[0].file_count, 3);
     }
 
+    #[test]
+    fn test_manifest_without_kind_defaults_to_message() {
+        // Manifests written before pre-restore snapshots existed have no `kind`
+        let json = r#"{
+            "conversationId": "conv-123",
+            "appId": "my-app",
+            "createdAt": "2025-01-01T00:00:00Z",
+            "updatedAt": "2025-01-01T00:00:00Z",
+            "snapshots": [{
+                "id": "snap-001",
+                "messageId": "msg-001",
+                "createdAt": "2025-01-01T00:00:00Z",
+                "fileCount": 1,
+                "totalBytes": 10
+            }]
+        }"#;
+
+        let parsed: CheckpointManifest = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.snapshots[0].kind, SnapshotKind::Message);
+    }
+
+    #[test]
+    fn test_pre_restore_snapshot_serialization() {
+        let snapshot = Snapshot {
+            id: "snap-pre-restore-1".to_string(),
+            message_id: "pre-restore-1".to_string(),
+            conversation_id: "conv-123".to_string(),
+            app_id: "my-app".to_string(),
+            app_dir: "/path/to/app".to_string(),
+            created_at: Utc::now(),
+            files: vec![],
+            kind: SnapshotKind::PreRestore,
+            restored_message_id: Some("msg-001".to_string()),
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
+        };
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains("\"kind\":\"pre-restore\""));
+        assert!(json.contains("\"restoredMessageId\":\"msg-001\""));
+
+        let parsed: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.kind, SnapshotKind::PreRestore);
+        assert_eq!(parsed.restored_message_id, Some("msg-001".to_string()));
+    }
+
     // ==================== BLOB STORAGE TESTS ====================
 
     #[test]
     fn test_blob_path_structure() {
         // This tests the path structure without needing actual filesystem
         let hash = "a1b2c3d4e5f6789012345678901234567890123456789012345678901234abcd";
-        let result = get_blob_path("my-app", "conv-123", hash);
+        let result = get_blob_path("my-app", hash);
 
         // Should return Ok if hash is long enough
         assert!(result.is_ok());
@@ -1075,7 +3408,7 @@ mod tests {
 
     #[test]
     fn test_blob_path_rejects_short_hash() {
-        let result = get_blob_path("my-app", "conv-123", "a");
+        let result = get_blob_path("my-app", "a");
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("too short"));
     }
@@ -1172,6 +3505,7 @@ mod tests {
                     size: 100,
                     mode: 0o644,
                     exists: true,
+                    binary: false,
                 },
                 FileEntry {
                     path: "deleted.ts".to_string(),
@@ -1179,8 +3513,14 @@ mod tests {
                     size: 0,
                     mode: 0,
                     exists: false,
+                    binary: false,
                 },
             ],
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
         };
 
         // Write snapshot
@@ -1253,6 +3593,183 @@ mod tests {
         assert!(already_exists); // We'd reuse the existing blob
     }
 
+    // ==================== SHARED BLOB STORE TESTS ====================
+
+    fn hashes(entries: &[&str]) -> BTreeSet<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_store_blob_at_path_deduplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+
+        let (hash, created) = store_blob_at_path(&store_dir, b"shared content").unwrap();
+        assert!(created);
+        let (same_hash, created_again) =
+            store_blob_at_path(&store_dir, b"shared content").unwrap();
+        assert!(!created_again);
+        assert_eq!(hash, same_hash);
+
+        assert_eq!(read_blob_at_path(&store_dir, &hash).unwrap(), b"shared content");
+    }
+
+    #[test]
+    fn test_blob_stored_compressed_with_stable_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        let content = "{\"fixture\": true}\n".repeat(1000);
+
+        let (hash, _) = store_blob_at_path(&store_dir, content.as_bytes()).unwrap();
+        assert_eq!(hash, hash_content(content.as_bytes()));
+
+        let on_disk = fs::read(blob_path_at_path(&store_dir, &hash).unwrap()).unwrap();
+        assert_eq!(&on_disk[..4], BLOB_MAGIC);
+        assert_eq!(on_disk[4], BLOB_CODEC_ZSTD);
+        assert!(on_disk.len() < content.len());
+
+        assert_eq!(read_blob_at_path(&store_dir, &hash).unwrap(), content.as_bytes());
+    }
+
+    #[test]
+    fn test_incompressible_blob_stored_raw_with_header() {
+        let encoded = encode_blob(b"x").unwrap();
+        assert_eq!(&encoded[..4], BLOB_MAGIC);
+        assert_eq!(encoded[4], BLOB_CODEC_RAW);
+        assert_eq!(decode_blob(encoded).unwrap(), b"x");
+    }
+
+    #[test]
+    fn test_empty_blob_round_trip() {
+        let encoded = encode_blob(b"").unwrap();
+        assert_eq!(decode_blob(encoded).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_legacy_uncompressed_blob_still_readable() {
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        let content = b"written before compression";
+        let hash = hash_content(content);
+
+        let blob_path = blob_path_at_path(&store_dir, &hash).unwrap();
+        fs::create_dir_all(blob_path.parent().unwrap()).unwrap();
+        fs::write(&blob_path, content).unwrap();
+
+        assert_eq!(read_blob_at_path(&store_dir, &hash).unwrap(), content);
+    }
+
+    #[test]
+    fn test_legacy_blob_starting_with_magic_is_returned_as_is() {
+        let legacy = b"MLDB\xffnot a header".to_vec();
+        assert_eq!(decode_blob(legacy.clone()).unwrap(), legacy);
+    }
+
+    #[test]
+    fn test_release_deletes_blob_only_when_unreferenced() {
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        let (hash, _) = store_blob_at_path(&store_dir, b"used twice").unwrap();
+        let stored_len = blob_path_at_path(&store_dir, &hash)
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .len();
+
+        // Two snapshots (e.g. in different conversations) reference the blob
+        retain_blobs_at_path(&store_dir, &hashes(&[&hash])).unwrap();
+        retain_blobs_at_path(&store_dir, &hashes(&[&hash])).unwrap();
+
+        let (deleted, _) = release_blobs_at_path(&store_dir, &hashes(&[&hash])).unwrap();
+        assert_eq!(deleted, 0);
+        assert!(read_blob_at_path(&store_dir, &hash).is_ok());
+
+        let (deleted, freed) = release_blobs_at_path(&store_dir, &hashes(&[&hash])).unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(freed, stored_len);
+        assert!(read_blob_at_path(&store_dir, &hash).is_err());
+        assert!(load_blob_refs_at_path(&store_dir).unwrap().refs.is_empty());
+    }
+
+    #[test]
+    fn test_release_ignores_unknown_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        let (hash, _) = store_blob_at_path(&store_dir, b"untracked").unwrap();
+
+        let (deleted, _) = release_blobs_at_path(&store_dir, &hashes(&[&hash])).unwrap();
+        assert_eq!(deleted, 0);
+        assert!(read_blob_at_path(&store_dir, &hash).is_ok());
+    }
+
+    #[test]
+    fn test_migrate_legacy_per_conversation_blobs() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("my-app");
+        let shared = b"same file in both conversations";
+        let only_b = b"only in conversation b";
+        let shared_hash = hash_content(shared);
+        let only_b_hash = hash_content(only_b);
+
+        for (conversation_id, blobs) in [
+            ("conv-a", vec![(&shared_hash, &shared[..])]),
+            ("conv-b", vec![(&shared_hash, &shared[..]), (&only_b_hash, &only_b[..])]),
+        ] {
+            let conversation_dir = app_checkpoints_dir.join(conversation_id);
+            let mut files = vec![];
+            for (hash, content) in blobs {
+                let blob_dir = conversation_dir.join("blobs").join(&hash[0..2]);
+                fs::create_dir_all(&blob_dir).unwrap();
+                fs::write(blob_dir.join(hash), content).unwrap();
+                files.push(FileEntry {
+                    path: format!("{}.ts", &hash[0..6]),
+                    hash: Some(hash.clone()),
+                    size: content.len() as u64,
+                    mode: 0o644,
+                    exists: true,
+                    binary: false,
+                });
+            }
+
+            let snapshot = Snapshot {
+                id: "snap-001".to_string(),
+                message_id: "msg-001".to_string(),
+                conversation_id: conversation_id.to_string(),
+                app_id: "my-app".to_string(),
+                app_dir: "/path/to/app".to_string(),
+                created_at: Utc::now(),
+                files,
+                kind: SnapshotKind::Message,
+                restored_message_id: None,
+                restored_snapshot_id: None,
+                label: None,
+                git_commit: None,
+            };
+            let snapshots_dir = conversation_dir.join("snapshots");
+            fs::create_dir_all(&snapshots_dir).unwrap();
+            fs::write(
+                snapshots_dir.join("msg-001.json"),
+                serde_json::to_string(&snapshot).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let store_dir = ensure_blob_store_at_path(&app_checkpoints_dir).unwrap();
+
+        assert!(!app_checkpoints_dir.join("conv-a/blobs").exists());
+        assert!(!app_checkpoints_dir.join("conv-b/blobs").exists());
+        assert_eq!(read_blob_at_path(&store_dir, &shared_hash).unwrap(), shared);
+        assert_eq!(read_blob_at_path(&store_dir, &only_b_hash).unwrap(), only_b);
+
+        let refs = load_blob_refs_at_path(&store_dir).unwrap();
+        assert_eq!(refs.refs.get(&shared_hash), Some(&2));
+        assert_eq!(refs.refs.get(&only_b_hash), Some(&1));
+
+        // Migration only runs once; the store is not treated as a conversation
+        assert_eq!(migrate_legacy_blobs_at_path(&app_checkpoints_dir).unwrap(), 0);
+        assert_eq!(checkpoint_subdirs(&app_checkpoints_dir).unwrap().len(), 2);
+    }
+
     // ==================== EDGE CASE TESTS ====================
 
     #[test]
@@ -1282,6 +3799,7 @@ mod tests {
             size: 100,
             mode: 0o644,
             exists: true,
+            binary: false,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -1299,6 +3817,7 @@ mod tests {
                 size: 100,
                 mode: 0o644,
                 exists: true,
+                binary: false,
             })
             .collect();
 
@@ -1310,6 +3829,11 @@ mod tests {
             app_dir: "/path/to/large/app".to_string(),
             created_at: Utc::now(),
             files,
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
         };
 
         let json = serde_json::to_string(&snapshot).unwrap();
@@ -1339,6 +3863,9 @@ mod tests {
                 file_count: 1,
                 total_bytes: 100,
                 has_changes: false,
+                kind: SnapshotKind::Message,
+                label: None,
+                layout: SnapshotLayout::ById,
             });
         }
 
@@ -1372,32 +3899,722 @@ mod tests {
     }
 
     #[test]
-    fn test_restore_result() {
-        let result = RestoreResult {
-            files_restored: 4,
-            files_deleted: 1,
-            bytes_written: 8192,
-        };
-
-        let json = serde_json::to_string(&result).unwrap();
-        let parsed: RestoreResult = serde_json::from_str(&json).unwrap();
-
-        assert_eq!(parsed.files_restored, 4);
-        assert_eq!(parsed.files_deleted, 1);
+    fn test_restore_result() {
+        let result = RestoreResult {
+            files_restored: 4,
+            files_deleted: 1,
+            bytes_written: 8192,
+            pre_restore_message_id: None,
+            pre_restore_snapshot_id: None,
+        };
+
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: RestoreResult = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.files_restored, 4);
+        assert_eq!(parsed.files_deleted, 1);
+    }
+
+    #[test]
+    fn test_cleanup_result() {
+        let result = CleanupResult {
+            snapshots_deleted: 10,
+            blobs_deleted: 25,
+            bytes_freed: 102400,
+        };
+
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: CleanupResult = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.bytes_freed, 102400);
+    }
+
+    // ==================== RETENTION TESTS ====================
+
+    /// Store one single-file snapshot per `(message_id, days_ago, content)`
+    fn seed_conversation_at_path(
+        app_checkpoints_dir: &Path,
+        conversation_id: &str,
+        snapshots: &[(&str, i64, &str)],
+        now: DateTime<Utc>,
+    ) {
+        let conversation_dir = app_checkpoints_dir.join(conversation_id);
+        let store_dir = ensure_blob_store_at_path(app_checkpoints_dir).unwrap();
+        let mut manifest =
+            load_or_create_manifest_at_path(&conversation_dir, "app", conversation_id).unwrap();
+
+        for (message_id, days_ago, content) in snapshots {
+            let created_at = now - chrono::Duration::days(*days_ago);
+            let (hash, _) = store_blob_at_path(&store_dir, content.as_bytes()).unwrap();
+            let snapshot = Snapshot {
+                id: format!("snap-{}", message_id),
+                message_id: message_id.to_string(),
+                conversation_id: conversation_id.to_string(),
+                app_id: "app".to_string(),
+                app_dir: "/app".to_string(),
+                created_at,
+                files: vec![FileEntry {
+                    path: "file.txt".to_string(),
+                    hash: Some(hash),
+                    size: content.len() as u64,
+                    mode: 0o644,
+                    exists: true,
+                    binary: false,
+                }],
+                kind: SnapshotKind::Message,
+                restored_message_id: None,
+                restored_snapshot_id: None,
+                label: None,
+                git_commit: None,
+            };
+            save_snapshot_at_path(&conversation_dir, &snapshot).unwrap();
+            retain_blobs_at_path(&store_dir, &snapshot_blob_hashes(&snapshot)).unwrap();
+            manifest.snapshots.push(CheckpointSummary {
+                id: snapshot.id.clone(),
+                message_id: message_id.to_string(),
+                created_at,
+                file_count: 1,
+                total_bytes: content.len() as u64,
+                has_changes: false,
+                kind: SnapshotKind::Message,
+                label: None,
+                layout: SnapshotLayout::ById,
+            });
+        }
+
+        save_manifest_at_path(&conversation_dir, &manifest).unwrap();
+    }
+
+    fn remaining_messages(conversation_dir: &Path) -> Vec<String> {
+        load_manifest_at_path(conversation_dir)
+            .unwrap()
+            .unwrap()
+            .snapshots
+            .into_iter()
+            .map(|summary| summary.message_id)
+            .collect()
+    }
+
+    /// Pseudo-random content, so every blob ends up about the same size on disk
+    fn noisy_content(seed: u8, len: usize) -> String {
+        let mut state = seed as u32 + 1;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                char::from(b'!' + ((state >> 16) % 90) as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_retention_policy_rules() {
+        let now = Utc::now();
+        let old = now - chrono::Duration::days(40);
+        let recent = now - chrono::Duration::days(1);
+
+        let both = RetentionPolicy::default();
+        assert!(!both.allows_removal(60, recent, now));
+        assert!(!both.allows_removal(10, old, now));
+        assert!(both.allows_removal(60, old, now));
+
+        let count_only = RetentionPolicy {
+            keep_last_n: Some(2),
+            max_age_days: None,
+            ..RetentionPolicy::default()
+        };
+        assert!(!count_only.allows_removal(1, old, now));
+        assert!(count_only.allows_removal(2, recent, now));
+
+        let age_only = RetentionPolicy {
+            keep_last_n: None,
+            max_age_days: Some(7),
+            ..RetentionPolicy::default()
+        };
+        assert!(age_only.allows_removal(0, old, now));
+        assert!(!age_only.allows_removal(50, recent, now));
+        assert!(!age_only.allows_removal(50, now + chrono::Duration::days(3), now));
+
+        let none = RetentionPolicy {
+            keep_last_n: None,
+            max_age_days: None,
+            ..RetentionPolicy::default()
+        };
+        assert!(!none.allows_removal(1000, old, now));
+    }
+
+    #[test]
+    fn test_retention_policy_deserializes_partial_preference() {
+        let policy: RetentionPolicy =
+            serde_json::from_value(serde_json::json!({ "maxBytesPerApp": 1024 })).unwrap();
+        assert_eq!(policy.keep_last_n, Some(DEFAULT_KEEP_LAST_N));
+        assert_eq!(policy.max_bytes_per_app, Some(1024));
+
+        let policy: RetentionPolicy =
+            serde_json::from_value(serde_json::json!({ "maxAgeDays": null })).unwrap();
+        assert_eq!(policy.max_age_days, None);
+    }
+
+    #[test]
+    fn test_gc_applies_count_and_age_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv-1",
+            &[("m1", 40, "one"), ("m2", 35, "two"), ("m3", 3, "three"), ("m4", 2, "four")],
+            now,
+        );
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv-2",
+            &[("m1", 90, "shared"), ("m2", 80, "shared")],
+            now,
+        );
+
+        let policy = RetentionPolicy {
+            keep_last_n: Some(1),
+            max_age_days: Some(30),
+            ..RetentionPolicy::default()
+        };
+        let result = collect_garbage_at_path(temp_dir.path(), &policy, now).unwrap();
+
+        assert_eq!(result.snapshots_deleted, 3);
+        // "shared" is still referenced by conv-2's latest snapshot
+        assert_eq!(result.blobs_deleted, 2);
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-1")), ["m3", "m4"]);
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-2")), ["m2"]);
+        assert!(!snapshot_path_at_path(&app_checkpoints_dir.join("conv-1"), "snap-m1").exists());
+    }
+
+    #[test]
+    fn test_gc_enforces_app_byte_cap_oldest_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+        let blob = |seed| noisy_content(seed, 10_000);
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv-1",
+            &[("a1", 5, &blob(1)), ("a2", 3, &blob(2)), ("a3", 1, &blob(3))],
+            now,
+        );
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv-2",
+            &[("b1", 4, &blob(4)), ("b2", 2, &blob(5))],
+            now,
+        );
+
+        // Five similar blobs: a cap of 3.5 blobs means dropping the two oldest (a1, b1)
+        let store_dir = app_checkpoints_dir.join(BLOB_STORE_DIR);
+        let blob_bytes = blob_store_bytes_at_path(&store_dir) / 5;
+        let policy = RetentionPolicy {
+            keep_last_n: None,
+            max_age_days: None,
+            max_bytes_per_app: Some(blob_bytes * 7 / 2),
+            max_bytes_per_workspace: None,
+        };
+        let result = collect_garbage_at_path(temp_dir.path(), &policy, now).unwrap();
+
+        assert_eq!(result.snapshots_deleted, 2);
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-1")), ["a2", "a3"]);
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-2")), ["b2"]);
+        assert!(blob_store_bytes_at_path(&store_dir) <= blob_bytes * 7 / 2);
+
+        // A cap nothing fits under still keeps each conversation's latest snapshot
+        let policy = RetentionPolicy {
+            max_bytes_per_app: Some(0),
+            ..policy
+        };
+        collect_garbage_at_path(temp_dir.path(), &policy, now).unwrap();
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-1")), ["a3"]);
+        assert_eq!(remaining_messages(&app_checkpoints_dir.join("conv-2")), ["b2"]);
+    }
+
+    #[test]
+    fn test_gc_enforces_workspace_byte_cap_across_apps() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let blob = |seed| noisy_content(seed, 10_000);
+        seed_conversation_at_path(
+            &temp_dir.path().join("notes"),
+            "conv",
+            &[("n1", 9, &blob(1)), ("n2", 1, &blob(2))],
+            now,
+        );
+        seed_conversation_at_path(
+            &temp_dir.path().join("todo"),
+            "conv",
+            &[("t1", 5, &blob(3)), ("t2", 2, &blob(4))],
+            now,
+        );
+
+        // Room for three and a half blobs: only the oldest snapshot (n1) goes
+        let blob_bytes = blob_store_bytes_at_path(&temp_dir.path().join("notes/.store")) / 2;
+        let policy = RetentionPolicy {
+            keep_last_n: None,
+            max_age_days: None,
+            max_bytes_per_app: None,
+            max_bytes_per_workspace: Some(blob_bytes * 7 / 2),
+        };
+        let result = collect_garbage_at_path(temp_dir.path(), &policy, now).unwrap();
+
+        assert_eq!(result.snapshots_deleted, 1);
+        assert_eq!(result.blobs_deleted, 1);
+        assert_eq!(remaining_messages(&temp_dir.path().join("notes/conv")), ["n2"]);
+        assert_eq!(remaining_messages(&temp_dir.path().join("todo/conv")), ["t1", "t2"]);
+    }
+
+    // ==================== INTEGRITY TESTS ====================
+
+    fn issue_kinds(result: &VerifyResult) -> Vec<(IntegrityIssueKind, Option<String>)> {
+        let mut kinds: Vec<_> = result
+            .issues
+            .iter()
+            .map(|issue| (issue.kind, issue.message_id.clone()))
+            .collect();
+        kinds.sort_by_key(|(kind, message_id)| (format!("{:?}", kind), message_id.clone()));
+        kinds
+    }
+
+    #[test]
+    fn test_verify_clean_store_has_no_issues() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv",
+            &[("m1", 2, "one"), ("m2", 1, "two")],
+            Utc::now(),
+        );
+
+        let result = verify_checkpoints_at_path(temp_dir.path(), None, false).unwrap();
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+        assert_eq!(result.apps_checked, 1);
+        assert_eq!(result.snapshots_checked, 2);
+        assert_eq!(result.blobs_checked, 2);
+        assert!(result.repair.is_none());
+    }
+
+    #[test]
+    fn test_verify_reports_and_repairs_damage() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        let store_dir = app_checkpoints_dir.join(BLOB_STORE_DIR);
+        seed_conversation_at_path(
+            &app_checkpoints_dir,
+            "conv",
+            &[("m1", 4, "one"), ("m2", 3, "two"), ("m3", 2, "three"), ("m4", 1, "four")],
+            Utc::now(),
+        );
+
+        // m1: snapshot file gone; m2: blob gone; m3: blob overwritten
+        fs::remove_file(snapshot_path_at_path(&conversation_dir, "snap-m1")).unwrap();
+        fs::remove_file(blob_path_at_path(&store_dir, &hash_content(b"two")).unwrap()).unwrap();
+        fs::write(blob_path_at_path(&store_dir, &hash_content(b"three")).unwrap(), b"x").unwrap();
+        // A blob nothing references, and a snapshot file the manifest doesn't list
+        store_blob_at_path(&store_dir, b"stray").unwrap();
+        let mut stray = load_snapshot_at_path(&conversation_dir, "snap-m4").unwrap();
+        stray.id = "snap-unlisted".to_string();
+        stray.message_id = "unlisted".to_string();
+        save_snapshot_at_path(&conversation_dir, &stray).unwrap();
+
+        let result = verify_checkpoints_at_path(temp_dir.path(), Some("app"), false).unwrap();
+        let m = |id: &str| Some(id.to_string());
+        assert_eq!(
+            issue_kinds(&result),
+            [
+                (IntegrityIssueKind::CorruptBlob, m("m3")),
+                (IntegrityIssueKind::MissingBlob, m("m2")),
+                (IntegrityIssueKind::MissingSnapshot, m("m1")),
+                // m1's "one" and the stray blob
+                (IntegrityIssueKind::OrphanedBlob, None),
+                (IntegrityIssueKind::OrphanedBlob, None),
+                (IntegrityIssueKind::OrphanedSnapshot, m("unlisted")),
+                (IntegrityIssueKind::RefCountMismatch, None),
+            ]
+        );
+        // Verification alone changes nothing
+        assert_eq!(remaining_messages(&conversation_dir), ["m1", "m2", "m3", "m4"]);
+
+        let result = verify_checkpoints_at_path(temp_dir.path(), Some("app"), true).unwrap();
+        let repair = result.repair.unwrap();
+        assert_eq!(repair.snapshots_deleted, 3);
+        // The corrupt "three" blob and the two orphans
+        assert_eq!(repair.blobs_deleted, 3);
+        assert_eq!(remaining_messages(&conversation_dir), ["m4"]);
+        assert!(!snapshot_path_at_path(&conversation_dir, "snap-m2").exists());
+
+        // Only the unlisted snapshot file is left to report
+        let result = verify_checkpoints_at_path(temp_dir.path(), Some("app"), false).unwrap();
+        assert_eq!(
+            issue_kinds(&result),
+            [(IntegrityIssueKind::OrphanedSnapshot, m("unlisted"))]
+        );
+        let refs = load_blob_refs_at_path(&store_dir).unwrap();
+        assert_eq!(refs.refs.get(&hash_content(b"four")), Some(&2));
+    }
+
+    #[test]
+    fn test_verify_reports_corrupt_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let conversation_dir = temp_dir.path().join("app").join("conv");
+        fs::create_dir_all(&conversation_dir).unwrap();
+        fs::write(conversation_dir.join("manifest.json"), "{not json").unwrap();
+
+        let result = verify_checkpoints_at_path(temp_dir.path(), None, true).unwrap();
+        assert_eq!(issue_kinds(&result), [(IntegrityIssueKind::CorruptManifest, None)]);
+        // Nothing to drop without a readable manifest
+        assert_eq!(result.repair.unwrap().snapshots_deleted, 0);
+        assert!(conversation_dir.join("manifest.json").exists());
+    }
+
+    // ==================== LOCKING TESTS ====================
+
+    #[test]
+    fn test_write_file_atomic_replaces_without_leftovers() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join("manifest.json");
+
+        write_file_atomic(&path, b"first", true).unwrap();
+        write_file_atomic(&path, b"second", false).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        let entries: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from("manifest.json")]);
+    }
+
+    #[test]
+    fn test_checkpoint_lock_is_exclusive() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+
+        let lock = acquire_checkpoint_lock_at_path(&app_checkpoints_dir).unwrap();
+        let lock_path = app_checkpoints_dir.join(CHECKPOINT_LOCK_FILE);
+        assert!(lock_path.exists());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waiter_dir = app_checkpoints_dir.clone();
+        let waiter = std::thread::spawn(move || {
+            let _lock = acquire_checkpoint_lock_at_path(&waiter_dir).unwrap();
+            tx.send(()).unwrap();
+        });
+
+        // The waiter can't get in while the lock is held
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(lock);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiter.join().unwrap();
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_stale_checkpoint_lock_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("app");
+        fs::create_dir_all(&app_checkpoints_dir).unwrap();
+
+        // A lock left behind by a crashed process
+        let lock_path = app_checkpoints_dir.join(CHECKPOINT_LOCK_FILE);
+        let file = fs::File::create(&lock_path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(600)).unwrap();
+        drop(file);
+
+        let start = Instant::now();
+        let _lock = acquire_checkpoint_lock_at_path(&app_checkpoints_dir).unwrap();
+        assert!(start.elapsed() < CHECKPOINT_LOCK_TIMEOUT);
+    }
+
+    #[test]
+    fn test_concurrent_captures_keep_manifests_consistent() {
+        let temp_dir = TempDir::new().unwrap();
+        let checkpoints_dir = temp_dir.path().join("checkpoints");
+        let app_checkpoints_dir = checkpoints_dir.join("app");
+        let app_dir = temp_dir.path().join("app-src");
+        fs::create_dir_all(app_dir.join("src")).unwrap();
+        for index in 0..10 {
+            fs::write(app_dir.join("src").join(format!("file{}.ts", index)), "initial").unwrap();
+        }
+
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let app_checkpoints_dir = app_checkpoints_dir.clone();
+                let app_dir = app_dir.clone();
+                std::thread::spawn(move || {
+                    let app_dir = app_dir.to_string_lossy().to_string();
+                    for round in 0..5 {
+                        // Keep the working tree changing under the other captures
+                        let path = Path::new(&app_dir).join("src").join(format!("t{}.ts", thread));
+                        fs::write(path, format!("thread {} round {}", thread, round)).unwrap();
+
+                        let conversation_id = if thread % 2 == 0 { "conv-a" } else { "conv-b" };
+                        let message_id = format!("msg-{}-{}", thread, round);
+                        let request = CaptureRequest {
+                            app_id: "app",
+                            app_dir: &app_dir,
+                            conversation_id,
+                            message_id: &message_id,
+                            kind: SnapshotKind::Message,
+                            label: None,
+                            restores: None,
+                            full_hash: false,
+                        };
+                        capture_snapshot(&app_checkpoints_dir, &request).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for (conversation_id, parity) in [("conv-a", 0), ("conv-b", 1)] {
+            let manifest = load_manifest_at_path(&app_checkpoints_dir.join(conversation_id))
+                .unwrap()
+                .unwrap();
+            let mut captured: Vec<String> =
+                manifest.snapshots.iter().map(|s| s.message_id.clone()).collect();
+            captured.sort();
+            let mut expected: Vec<String> = (0..8)
+                .filter(|thread| thread % 2 == parity)
+                .flat_map(|thread| (0..5).map(move |round| format!("msg-{}-{}", thread, round)))
+                .collect();
+            expected.sort();
+            assert_eq!(captured, expected);
+        }
+
+        let result = verify_checkpoints_at_path(&checkpoints_dir, None, false).unwrap();
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+        assert_eq!(result.snapshots_checked, 40);
+        assert!(!app_checkpoints_dir.join(CHECKPOINT_LOCK_FILE).exists());
+    }
+
+    // ==================== SNAPSHOT ID TESTS ====================
+
+    fn capture_at_path(
+        app_checkpoints_dir: &Path,
+        app_dir: &Path,
+        message_id: &str,
+        label: Option<&str>,
+    ) -> CheckpointResult {
+        let app_dir = app_dir.to_string_lossy().to_string();
+        let request = CaptureRequest {
+            app_id: "app",
+            app_dir: &app_dir,
+            conversation_id: "conv",
+            message_id,
+            kind: SnapshotKind::Message,
+            label: label.map(str::to_string),
+            restores: None,
+            full_hash: false,
+        };
+        capture_snapshot(app_checkpoints_dir, &request).unwrap().1
+    }
+
+    #[test]
+    fn test_snapshot_ids_are_unique_per_capture() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("checkpoints").join("app");
+        let app_dir = temp_dir.path().join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("index.ts"), "v1").unwrap();
+
+        // Message IDs sharing their first 8 characters used to share a snapshot ID
+        let results = [
+            capture_at_path(&app_checkpoints_dir, &app_dir, "message-0001", None),
+            capture_at_path(&app_checkpoints_dir, &app_dir, "message-0002", None),
+            capture_at_path(&app_checkpoints_dir, &app_dir, "message-0002", Some("tool 1")),
+            capture_at_path(&app_checkpoints_dir, &app_dir, "message-0002", Some("tool 2")),
+        ];
+        let ids: HashSet<&str> = results.iter().map(|result| result.id.as_str()).collect();
+        assert_eq!(ids.len(), 4);
+
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        let manifest = load_manifest_at_path(&conversation_dir).unwrap().unwrap();
+        assert_eq!(manifest.snapshots.len(), 4);
+        for (summary, result) in manifest.snapshots.iter().zip(&results) {
+            assert_eq!(summary.id, result.id);
+            assert_eq!(summary.layout, SnapshotLayout::ById);
+            let snapshot = load_snapshot_at_path(&conversation_dir, summary.file_key()).unwrap();
+            assert_eq!(snapshot.id, result.id);
+            assert_eq!(snapshot.label, summary.label);
+        }
+        assert_eq!(manifest.snapshots[2].label.as_deref(), Some("tool 1"));
+
+        // A message resolves to its latest snapshot; an ID to exactly that one
+        let latest = find_snapshot_index(&manifest, None, Some("message-0002")).unwrap();
+        assert_eq!(latest, 3);
+        let by_id = find_snapshot_index(&manifest, Some(&results[1].id), Some("ignored")).unwrap();
+        assert_eq!(by_id, 1);
+        assert!(find_snapshot_index(&manifest, Some("snap-missing"), None).is_err());
+        assert!(find_snapshot_index(&manifest, None, None).is_err());
+    }
+
+    #[test]
+    fn test_restore_target_by_snapshot_id_within_a_message() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("checkpoints").join("app");
+        let app_dir = temp_dir.path().join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("a.ts"), "a").unwrap();
+
+        let before_tool = capture_at_path(&app_checkpoints_dir, &app_dir, "msg", Some("tool 1"));
+        fs::write(app_dir.join("b.ts"), "b").unwrap();
+        capture_at_path(&app_checkpoints_dir, &app_dir, "msg", Some("tool 2"));
+
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        let manifest = load_manifest_at_path(&conversation_dir).unwrap().unwrap();
+
+        // Restoring the first snapshot of the message must undo what came after it
+        let index = find_snapshot_index(&manifest, Some(&before_tool.id), None).unwrap();
+        assert_eq!(index, 0);
+        let later = collect_later_checkpoint_paths(&conversation_dir, &manifest, index);
+        assert!(later.contains("b.ts"));
+
+        let latest = find_snapshot_index(&manifest, None, Some("msg")).unwrap();
+        assert!(collect_later_checkpoint_paths(&conversation_dir, &manifest, latest).is_empty());
+    }
+
+    #[test]
+    fn test_legacy_snapshots_stay_readable() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("checkpoints").join("app");
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        fs::create_dir_all(conversation_dir.join("snapshots")).unwrap();
+
+        // Two messages whose legacy snapshot IDs collide, stored by message ID
+        let mut summaries = vec![];
+        for message_id in ["message-0001", "message-0002"] {
+            let snapshot = serde_json::json!({
+                "id": "snap-message-",
+                "messageId": message_id,
+                "conversationId": "conv",
+                "appId": "app",
+                "appDir": "/app",
+                "createdAt": Utc::now(),
+                "files": [],
+            });
+            fs::write(
+                snapshot_path_at_path(&conversation_dir, message_id),
+                snapshot.to_string(),
+            )
+            .unwrap();
+            summaries.push(serde_json::json!({
+                "id": "snap-message-",
+                "messageId": message_id,
+                "createdAt": Utc::now(),
+                "fileCount": 0,
+                "totalBytes": 0,
+            }));
+        }
+        let manifest = serde_json::json!({
+            "conversationId": "conv",
+            "appId": "app",
+            "createdAt": Utc::now(),
+            "updatedAt": Utc::now(),
+            "snapshots": summaries,
+        });
+        fs::write(conversation_dir.join("manifest.json"), manifest.to_string()).unwrap();
+
+        let manifest = load_manifest_at_path(&conversation_dir).unwrap().unwrap();
+        assert!(manifest.snapshots.iter().all(|s| s.layout == SnapshotLayout::ByMessage));
+        let index = find_snapshot_index(&manifest, None, Some("message-0001")).unwrap();
+        let snapshot =
+            load_snapshot_at_path(&conversation_dir, manifest.snapshots[index].file_key()).unwrap();
+        assert_eq!(snapshot.message_id, "message-0001");
+
+        // New captures append to the legacy manifest without disturbing it
+        let app_dir = temp_dir.path().join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        let result = capture_at_path(&app_checkpoints_dir, &app_dir, "message-0001", None);
+        assert_ne!(result.id, "snap-message-");
+
+        let result = verify_checkpoints_at_path(&temp_dir.path().join("checkpoints"), None, false)
+            .unwrap();
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+        assert_eq!(result.snapshots_checked, 3);
+    }
+
+    // ==================== GIT STORAGE TESTS ====================
+
+    /// An app directory in a fresh git repository that opts into git storage
+    fn git_app_dir(root: &Path) -> PathBuf {
+        let app_dir = root.join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        let status = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .arg(&app_dir)
+            .env_remove("GIT_DIR")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::write(app_dir.join("moldable.json"), r#"{"checkpoint": {"storage": "git"}}"#).unwrap();
+        app_dir
+    }
+
+    #[test]
+    fn test_git_storage_commits_and_restores() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("checkpoints").join("app");
+        let app_dir = git_app_dir(temp_dir.path());
+        fs::write(app_dir.join("page.tsx"), "v1").unwrap();
+
+        let first = capture_at_path(&app_checkpoints_dir, &app_dir, "m1", None);
+        fs::write(app_dir.join("page.tsx"), "v2").unwrap();
+        fs::write(app_dir.join("extra.ts"), "added").unwrap();
+        capture_at_path(&app_checkpoints_dir, &app_dir, "m2", None);
+        assert_eq!(first.blobs_created, 0);
+
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        let manifest = load_manifest_at_path(&conversation_dir).unwrap().unwrap();
+        let snapshot = load_snapshot_at_path(&conversation_dir, &first.id).unwrap();
+        let repo = GitRepo::discover(&app_dir).unwrap();
+        assert!(repo.has_commit(snapshot.git_commit.as_deref().unwrap()));
+        assert!(snapshot_blob_hashes(&snapshot).is_empty());
+        assert_eq!(blob_store_bytes_at_path(&app_checkpoints_dir.join(BLOB_STORE_DIR)), 0);
+
+        let later = collect_later_checkpoint_paths(&conversation_dir, &manifest, 0);
+        let source = ContentSource::for_snapshot(&app_checkpoints_dir, &snapshot).unwrap();
+        let result =
+            restore_snapshot_files(&app_dir, &snapshot, &later, None, |hash| source.read(hash))
+                .unwrap();
+        assert_eq!(result.files_deleted, 1);
+        assert_eq!(fs::read_to_string(app_dir.join("page.tsx")).unwrap(), "v1");
+        assert!(!app_dir.join("extra.ts").exists());
+
+        let checkpoints_dir = temp_dir.path().join("checkpoints");
+        let result = verify_checkpoints_at_path(&checkpoints_dir, None, false).unwrap();
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+
+        let mut broken = snapshot.clone();
+        broken.git_commit = Some("0".repeat(40));
+        save_snapshot_at_path(&conversation_dir, &broken).unwrap();
+        let result = verify_checkpoints_at_path(&checkpoints_dir, None, false).unwrap();
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].kind, IntegrityIssueKind::MissingCommit);
     }
 
     #[test]
-    fn test_cleanup_result() {
-        let result = CleanupResult {
-            snapshots_deleted: 10,
-            blobs_deleted: 25,
-            bytes_freed: 102400,
-        };
+    fn test_git_storage_falls_back_outside_a_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_checkpoints_dir = temp_dir.path().join("checkpoints").join("app");
+        let app_dir = temp_dir.path().join("app-src");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("moldable.json"), r#"{"checkpoint": {"storage": "git"}}"#).unwrap();
+        fs::write(app_dir.join("page.tsx"), "v1").unwrap();
 
-        let json = serde_json::to_string(&result).unwrap();
-        let parsed: CleanupResult = serde_json::from_str(&json).unwrap();
+        let result = capture_at_path(&app_checkpoints_dir, &app_dir, "m1", None);
+        assert_eq!(result.blobs_created, 2);
 
-        assert_eq!(parsed.bytes_freed, 102400);
+        let conversation_dir = app_checkpoints_dir.join("conv");
+        let snapshot = load_snapshot_at_path(&conversation_dir, &result.id).unwrap();
+        assert!(snapshot.git_commit.is_none());
+        assert_eq!(snapshot_blob_hashes(&snapshot).len(), 2);
     }
 
     // ==================== CASCADING REVERT TESTS ====================
@@ -1414,6 +4631,7 @@ mod tests {
                 size: 100,
                 mode: 0o644,
                 exists: true,
+                binary: false,
             }),
         ].into_iter().collect();
 
@@ -1451,6 +4669,9 @@ mod tests {
                     file_count: 1,
                     total_bytes: 100,
                     has_changes: true, // A differs from B -> A has undo
+                    kind: SnapshotKind::Message,
+                    label: None,
+                    layout: SnapshotLayout::ById,
                 },
                 CheckpointSummary {
                     id: "snap-B".to_string(),
@@ -1459,6 +4680,9 @@ mod tests {
                     file_count: 2,
                     total_bytes: 200,
                     has_changes: true, // B differs from C -> B has undo
+                    kind: SnapshotKind::Message,
+                    label: None,
+                    layout: SnapshotLayout::ById,
                 },
                 CheckpointSummary {
                     id: "snap-C".to_string(),
@@ -1467,6 +4691,9 @@ mod tests {
                     file_count: 3,
                     total_bytes: 300,
                     has_changes: false, // C is last -> no undo yet
+                    kind: SnapshotKind::Message,
+                    label: None,
+                    layout: SnapshotLayout::ById,
                 },
             ],
         };
@@ -1517,6 +4744,7 @@ mod tests {
                 size: 50,
                 mode: 0o644,
                 exists: true,
+                binary: false,
             }),
         ].into_iter().collect();
 
@@ -1549,4 +4777,683 @@ mod tests {
         assert!(!app_dir.join("global.css").exists());
         assert!(!app_dir.join("header.tsx").exists());
     }
+
+    // ==================== DIFF TESTS ====================
+
+    fn file_map(entries: &[(&str, &str)]) -> BTreeMap<String, FileVersion> {
+        entries
+            .iter()
+            .map(|(path, content)| {
+                let version = FileVersion {
+                    hash: hash_content(content.as_bytes()),
+                    binary: false,
+                };
+                (path.to_string(), version)
+            })
+            .collect()
+    }
+
+    fn load_from(contents: &[(&str, &str)]) -> impl Fn(&str, &str) -> Result<Vec<u8>, String> {
+        let by_path: std::collections::HashMap<String, Vec<u8>> = contents
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+            .collect();
+        move |path: &str, _hash: &str| {
+            by_path
+                .get(path)
+                .cloned()
+                .ok_or_else(|| format!("missing {}", path))
+        }
+    }
+
+    #[test]
+    fn test_diff_file_maps_classifies_changes() {
+        let from = [("a.ts", "one\n"), ("b.ts", "keep\n"), ("c.ts", "gone\n")];
+        let to = [("a.ts", "two\n"), ("b.ts", "keep\n"), ("d.ts", "new\n")];
+
+        let diffs =
+            diff_file_maps(&file_map(&from), &file_map(&to), load_from(&from), load_from(&to))
+                .unwrap();
+
+        let summary: Vec<(&str, FileChangeKind)> =
+            diffs.iter().map(|d| (d.path.as_str(), d.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a.ts", FileChangeKind::Modified),
+                ("c.ts", FileChangeKind::Deleted),
+                ("d.ts", FileChangeKind::Added),
+            ]
+        );
+        assert!(diffs[1].new_hash.is_none());
+        assert!(diffs[2].old_hash.is_none());
+    }
+
+    #[test]
+    fn test_diff_file_maps_identical_is_empty() {
+        let files = [("a.ts", "same\n")];
+        let diffs = diff_file_maps(
+            &file_map(&files),
+            &file_map(&files),
+            load_from(&files),
+            load_from(&files),
+        )
+        .unwrap();
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn test_diff_file_maps_binary_change_has_no_hunks() {
+        let mut from = file_map(&[("icon.png", "old pixels")]);
+        let mut to = file_map(&[("icon.png", "new pixels")]);
+        from.get_mut("icon.png").unwrap().binary = true;
+        to.get_mut("icon.png").unwrap().binary = true;
+
+        let never_load = |_: &str, _: &str| -> Result<Vec<u8>, String> {
+            Err("binary content should not be loaded".to_string())
+        };
+        let diffs = diff_file_maps(&from, &to, never_load, never_load).unwrap();
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].change, FileChangeKind::Modified);
+        assert!(diffs[0].binary);
+        assert!(diffs[0].hunks.is_empty());
+    }
+
+    #[test]
+    fn test_unified_hunks_text() {
+        let hunks = unified_hunks(b"a\nb\nc\n", b"a\nB\nc\n");
+        assert_eq!(hunks.len(), 1);
+        assert!(hunks[0].header.starts_with("@@"));
+        assert_eq!(hunks[0].lines, vec![" a", "-b", "+B", " c"]);
+    }
+
+    #[test]
+    fn test_unified_hunks_skips_non_utf8() {
+        let hunks = unified_hunks(&[0xff, 0xfe, 0x00], b"text\n");
+        assert!(hunks.is_empty());
+    }
+
+    #[test]
+    fn test_file_version_map_skips_deleted_entries() {
+        let snapshot = Snapshot {
+            id: "snap-001".to_string(),
+            message_id: "msg-001".to_string(),
+            conversation_id: "conv-001".to_string(),
+            app_id: "app".to_string(),
+            app_dir: "/app".to_string(),
+            created_at: Utc::now(),
+            files: vec![
+                FileEntry {
+                    path: "kept.ts".to_string(),
+                    hash: Some("hash1".to_string()),
+                    size: 10,
+                    mode: 0o644,
+                    exists: true,
+                    binary: false,
+                },
+                FileEntry {
+                    path: "deleted.ts".to_string(),
+                    hash: None,
+                    size: 0,
+                    mode: 0,
+                    exists: false,
+                    binary: false,
+                },
+            ],
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
+        };
+
+        let map = file_version_map(&snapshot.files);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("kept.ts").map(|v| v.hash.as_str()), Some("hash1"));
+    }
+
+    #[test]
+    fn test_scan_working_tree_hashes_source_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(temp_dir.path().join("src/index.ts"), "export {}").unwrap();
+        fs::write(temp_dir.path().join("node_modules/pkg/index.js"), "ignored").unwrap();
+
+        let scan =
+            scan_working_tree(temp_dir.path(), &StatCache::new(temp_dir.path()), None).unwrap();
+        assert_eq!(scan.files.len(), 1);
+        assert_eq!(
+            scan.files[0].path,
+            format!("src{}index.ts", std::path::MAIN_SEPARATOR)
+        );
+        assert_eq!(scan.files[0].hash, Some(hash_content(b"export {}")));
+    }
+
+    // ==================== SELECTIVE RESTORE TESTS ====================
+
+    /// Build a snapshot of `files` in `app_dir`, returning it with an in-memory blob store
+    fn snapshot_of(
+        app_dir: &Path,
+        files: &[(&str, Option<&str>)],
+    ) -> (Snapshot, std::collections::HashMap<String, Vec<u8>>) {
+        let mut blobs = std::collections::HashMap::new();
+        let entries = files
+            .iter()
+            .map(|(path, content)| match content {
+                Some(content) => {
+                    let hash = hash_content(content.as_bytes());
+                    blobs.insert(hash.clone(), content.as_bytes().to_vec());
+                    FileEntry {
+                        path: path.to_string(),
+                        hash: Some(hash),
+                        size: content.len() as u64,
+                        mode: 0,
+                        exists: true,
+                        binary: false,
+                    }
+                }
+                None => FileEntry {
+                    path: path.to_string(),
+                    hash: None,
+                    size: 0,
+                    mode: 0,
+                    exists: false,
+                    binary: false,
+                },
+            })
+            .collect();
+
+        let snapshot = Snapshot {
+            id: "snap-001".to_string(),
+            message_id: "msg-001".to_string(),
+            conversation_id: "conv-001".to_string(),
+            app_id: "app".to_string(),
+            app_dir: app_dir.to_string_lossy().to_string(),
+            created_at: Utc::now(),
+            files: entries,
+            kind: SnapshotKind::Message,
+            restored_message_id: None,
+            restored_snapshot_id: None,
+            label: None,
+            git_commit: None,
+        };
+        (snapshot, blobs)
+    }
+
+    fn paths(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_selective_restore_only_touches_selected_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        fs::create_dir_all(app_dir.join("src")).unwrap();
+
+        let (snapshot, blobs) = snapshot_of(
+            app_dir,
+            &[("src/button.tsx", Some("old button")), ("src/header.tsx", Some("old header"))],
+        );
+        fs::write(app_dir.join("src/button.tsx"), "new button").unwrap();
+        fs::write(app_dir.join("src/header.tsx"), "new header").unwrap();
+
+        let selection = PathSelection::parse(app_dir, &paths(&["src/button.tsx"])).unwrap();
+        let result = restore_snapshot_files(
+            app_dir,
+            &snapshot,
+            &HashSet::new(),
+            Some(&selection),
+            |hash| Ok(blobs[hash].clone()),
+        )
+        .unwrap();
+
+        assert_eq!(result.files_restored, 1);
+        assert_eq!(result.files_deleted, 0);
+        assert_eq!(result.bytes_written, "old button".len() as u64);
+        assert_eq!(fs::read_to_string(app_dir.join("src/button.tsx")).unwrap(), "old button");
+        assert_eq!(fs::read_to_string(app_dir.join("src/header.tsx")).unwrap(), "new header");
+    }
+
+    #[test]
+    fn test_selective_restore_glob_deletes_later_files_in_scope() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        fs::create_dir_all(app_dir.join("src/components")).unwrap();
+
+        let (snapshot, blobs) =
+            snapshot_of(app_dir, &[("src/components/card.tsx", Some("old card"))]);
+        fs::write(app_dir.join("src/components/card.tsx"), "new card").unwrap();
+        fs::write(app_dir.join("src/components/modal.tsx"), "added later").unwrap();
+        fs::write(app_dir.join("src/page.tsx"), "added later").unwrap();
+
+        let later: HashSet<String> = ["src/components/modal.tsx", "src/page.tsx"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let selection =
+            PathSelection::parse(app_dir, &paths(&["src/components/*.tsx"])).unwrap();
+        let result =
+            restore_snapshot_files(app_dir, &snapshot, &later, Some(&selection), |hash| {
+                Ok(blobs[hash].clone())
+            })
+            .unwrap();
+
+        assert_eq!(result.files_restored, 1);
+        assert_eq!(result.files_deleted, 1);
+        assert!(!app_dir.join("src/components/modal.tsx").exists());
+        assert!(app_dir.join("src/page.tsx").exists());
+    }
+
+    #[test]
+    fn test_selective_restore_verifies_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        let (snapshot, _) = snapshot_of(app_dir, &[("a.ts", Some("original"))]);
+        fs::write(app_dir.join("a.ts"), "current").unwrap();
+
+        let selection = PathSelection::parse(app_dir, &paths(&["a.ts"])).unwrap();
+        let result = restore_snapshot_files(
+            app_dir,
+            &snapshot,
+            &HashSet::new(),
+            Some(&selection),
+            |_| Ok(b"tampered".to_vec()),
+        );
+
+        assert!(result.unwrap_err().contains("Hash mismatch"));
+        assert_eq!(fs::read_to_string(app_dir.join("a.ts")).unwrap(), "current");
+    }
+
+    #[test]
+    fn test_selective_restore_no_match_is_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        let (snapshot, blobs) = snapshot_of(app_dir, &[("a.ts", Some("a"))]);
+
+        let selection = PathSelection::parse(app_dir, &paths(&["missing.ts"])).unwrap();
+        let result = restore_snapshot_files(
+            app_dir,
+            &snapshot,
+            &HashSet::new(),
+            Some(&selection),
+            |hash| Ok(blobs[hash].clone()),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_redo_reapplies_pre_restore_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+
+        // Working tree before the restore: edited page, plus a file the restore removed
+        let (mut pre_restore, pre_blobs) = snapshot_of(
+            app_dir,
+            &[("page.tsx", Some("newer page")), ("extra.tsx", Some("newer extra"))],
+        );
+        pre_restore.kind = SnapshotKind::PreRestore;
+
+        // State after restoring an older checkpoint that also had an old helper file
+        fs::write(app_dir.join("page.tsx"), "older page").unwrap();
+        fs::write(app_dir.join("helper.ts"), "older helper").unwrap();
+        let restored_paths: HashSet<String> = ["page.tsx", "helper.ts"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+
+        let result =
+            restore_snapshot_files(app_dir, &pre_restore, &restored_paths, None, |hash| {
+                Ok(pre_blobs[hash].clone())
+            })
+            .unwrap();
+
+        assert_eq!(result.files_restored, 2);
+        assert_eq!(result.files_deleted, 1);
+        assert_eq!(fs::read_to_string(app_dir.join("page.tsx")).unwrap(), "newer page");
+        assert_eq!(fs::read_to_string(app_dir.join("extra.tsx")).unwrap(), "newer extra");
+        assert!(!app_dir.join("helper.ts").exists());
+    }
+
+    #[test]
+    fn test_path_selection_matching() {
+        let temp_dir = TempDir::new().unwrap();
+        let selection = PathSelection::parse(
+            temp_dir.path(),
+            &paths(&["./src/lib/", "**/*.css", "README.md"]),
+        )
+        .unwrap();
+
+        assert!(selection.matches("src/lib/util.ts"));
+        assert!(selection.matches("src/lib/nested/deep.ts"));
+        assert!(selection.matches("styles/global.css"));
+        assert!(selection.matches("README.md"));
+        assert!(!selection.matches("src/library.ts"));
+        assert!(!selection.matches("src/page.tsx"));
+    }
+
+    #[test]
+    fn test_path_selection_rejects_traversal() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(PathSelection::parse(temp_dir.path(), &paths(&["../outside.ts"])).is_err());
+        assert!(PathSelection::parse(temp_dir.path(), &paths(&["../**/*.ts"])).is_err());
+        assert!(PathSelection::parse(temp_dir.path(), &paths(&["/etc/*"])).is_err());
+        assert!(PathSelection::parse(temp_dir.path(), &paths(&[])).is_err());
+    }
+
+    // ==================== STAT CACHE TESTS ====================
+
+    /// Write a file and backdate its mtime so it falls outside the racy window
+    fn write_settled(path: &Path, content: &[u8]) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+        let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+    }
+
+    #[test]
+    fn test_stat_cache_skips_unchanged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        write_settled(&app_dir.join("a.ts"), b"a");
+        write_settled(&app_dir.join("b.ts"), b"b");
+
+        let cold =
+            scan_working_tree(&app_dir, &StatCache::new(&app_dir), Some(&store_dir)).unwrap();
+        assert_eq!(cold.files_hashed, 2);
+        assert_eq!(cold.blobs_created, 2);
+
+        let warm = scan_working_tree(&app_dir, &cold.cache, Some(&store_dir)).unwrap();
+        assert_eq!(warm.files_hashed, 0);
+        assert_eq!(warm.blobs_reused, 2);
+        assert_eq!(
+            warm.files.iter().map(|f| &f.hash).collect::<Vec<_>>(),
+            cold.files.iter().map(|f| &f.hash).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_stat_cache_rehashes_modified_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        write_settled(&app_dir.join("a.ts"), b"before");
+        let cold = scan_working_tree(app_dir, &StatCache::new(app_dir), None).unwrap();
+
+        write_settled(&app_dir.join("a.ts"), b"after, longer");
+        let warm = scan_working_tree(app_dir, &cold.cache, None).unwrap();
+
+        assert_eq!(warm.files_hashed, 1);
+        assert_eq!(warm.files[0].hash, Some(hash_content(b"after, longer")));
+    }
+
+    #[test]
+    fn test_stat_cache_ignores_recently_modified_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        fs::write(app_dir.join("fresh.ts"), "just written").unwrap();
+
+        let scan = scan_working_tree(app_dir, &StatCache::new(app_dir), None).unwrap();
+        assert_eq!(scan.files.len(), 1);
+        assert!(scan.cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_stat_cache_rereads_when_blob_missing() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        write_settled(&app_dir.join("a.ts"), b"content");
+
+        let cold =
+            scan_working_tree(&app_dir, &StatCache::new(&app_dir), Some(&store_dir)).unwrap();
+        let hash = cold.files[0].hash.clone().unwrap();
+        fs::remove_file(blob_path_at_path(&store_dir, &hash).unwrap()).unwrap();
+
+        let warm = scan_working_tree(&app_dir, &cold.cache, Some(&store_dir)).unwrap();
+        assert_eq!(warm.files_hashed, 1);
+        assert_eq!(warm.blobs_created, 1);
+        assert!(read_blob_at_path(&store_dir, &hash).is_ok());
+    }
+
+    #[test]
+    fn test_stat_cache_round_trip_and_app_dir_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        let cache_path = temp_dir.path().join(STAT_CACHE_FILE);
+        write_settled(&app_dir.join("a.ts"), b"a");
+
+        let scan = scan_working_tree(&app_dir, &StatCache::new(&app_dir), None).unwrap();
+        save_stat_cache_at_path(&cache_path, &scan.cache).unwrap();
+
+        let loaded = load_stat_cache_at_path(&cache_path, &app_dir);
+        assert_eq!(loaded.entries.len(), 1);
+
+        let other = load_stat_cache_at_path(&cache_path, &temp_dir.path().join("other"));
+        assert!(other.entries.is_empty());
+
+        fs::write(&cache_path, "not json").unwrap();
+        assert!(load_stat_cache_at_path(&cache_path, &app_dir).entries.is_empty());
+    }
+
+    #[test]
+    fn test_stat_cache_benchmark() {
+        const FILE_COUNT: usize = 400;
+        const FILE_BYTES: usize = 32 * 1024;
+
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        for i in 0..FILE_COUNT {
+            let content = format!("// file {}\n{}", i, "x".repeat(FILE_BYTES));
+            write_settled(
+                &app_dir.join(format!("src/dir{}/file{}.ts", i % 20, i)),
+                content.as_bytes(),
+            );
+        }
+
+        // Populate the blob store so both timed runs only differ in hashing
+        let cold =
+            scan_working_tree(&app_dir, &StatCache::new(&app_dir), Some(&store_dir)).unwrap();
+
+        let started = std::time::Instant::now();
+        let full =
+            scan_working_tree(&app_dir, &StatCache::new(&app_dir), Some(&store_dir)).unwrap();
+        let full_elapsed = started.elapsed();
+
+        let started = std::time::Instant::now();
+        let cached = scan_working_tree(&app_dir, &cold.cache, Some(&store_dir)).unwrap();
+        let cached_elapsed = started.elapsed();
+
+        println!(
+            "{} files: full hash {:?}, stat cache {:?}",
+            FILE_COUNT, full_elapsed, cached_elapsed
+        );
+
+        assert_eq!(full.files_hashed, FILE_COUNT);
+        assert_eq!(cached.files_hashed, 0);
+        assert_eq!(cached.files.len(), FILE_COUNT);
+        assert!(cached_elapsed < full_elapsed);
+    }
+
+    // ==================== IGNORE RULE TESTS ====================
+
+    #[test]
+    fn test_ignore_rule_parse() {
+        assert!(IgnoreRule::parse("").is_none());
+        assert!(IgnoreRule::parse("   ").is_none());
+        assert!(IgnoreRule::parse("# comment").is_none());
+        assert!(IgnoreRule::parse("/").is_none());
+
+        let rule = IgnoreRule::parse("!generated/").unwrap();
+        assert!(rule.negated);
+        assert!(rule.dir_only);
+    }
+
+    #[test]
+    fn test_ignore_rule_anchoring() {
+        let unanchored = IgnoreRule::parse("*.log").unwrap();
+        assert!(unanchored.matches("debug.log", false));
+        assert!(unanchored.matches("logs/nested/debug.log", false));
+
+        let anchored = IgnoreRule::parse("/config.local.ts").unwrap();
+        assert!(anchored.matches("config.local.ts", false));
+        assert!(!anchored.matches("src/config.local.ts", false));
+
+        let nested = IgnoreRule::parse("src/generated").unwrap();
+        assert!(nested.matches("src/generated", true));
+        assert!(!nested.matches("lib/src/generated", true));
+
+        let dir_only = IgnoreRule::parse("out/").unwrap();
+        assert!(dir_only.matches("out", true));
+        assert!(!dir_only.matches("out", false));
+    }
+
+    #[test]
+    fn test_scan_rules_defaults() {
+        let rules = ScanRules::default();
+        assert!(rules.is_ignored("node_modules", true));
+        assert!(rules.is_ignored("packages/web/.next", true));
+        assert!(!rules.is_ignored("public/icon.svg", false));
+        assert!(rules.is_ignored("Cargo.lock", false));
+        assert!(!rules.is_ignored("src/page.tsx", false));
+        assert!(!rules.is_ignored("src", true));
+    }
+
+    #[test]
+    fn test_scan_rules_last_match_wins() {
+        let config = CheckpointConfig {
+            include: vec!["public/logo.png".to_string()],
+            exclude: vec!["fixtures/".to_string()],
+            max_binary_bytes: None,
+            ..Default::default()
+        };
+        let rules = ScanRules::from_sources(
+            "generated/\n*.local.ts\n",
+            "!yarn.lock\n!generated/keep/\ndata/*.json\n*.png\n",
+            &config,
+        );
+
+        // .gitignore excludes
+        assert!(rules.is_ignored("generated", true));
+        assert!(rules.is_ignored("src/env.local.ts", false));
+        // .moldableignore includes override defaults and .gitignore
+        assert!(!rules.is_ignored("yarn.lock", false));
+        assert!(rules.is_ignored("Cargo.lock", false));
+        assert!(!rules.is_ignored("generated/keep", true));
+        // .moldableignore excludes
+        assert!(rules.is_ignored("data/seed.json", false));
+        // moldable.json exclude and include
+        assert!(rules.is_ignored("fixtures", true));
+        assert!(!rules.is_ignored("public/logo.png", false));
+        assert!(rules.is_ignored("public/other.png", false));
+    }
+
+    #[test]
+    fn test_scan_source_files_honors_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path();
+        for (path, content) in [
+            (".gitignore", "generated/\n"),
+            (".moldableignore", "!yarn.lock\n"),
+            (
+                "moldable.json",
+                r#"{"name": "App", "checkpoint": {"include": ["dist/"], "exclude": ["*.snap"]}}"#,
+            ),
+            ("src/page.tsx", "page"),
+            ("src/page.test.snap", "snapshot"),
+            ("generated/client.ts", "generated"),
+            ("public/icon.svg", "<svg/>"),
+            ("public/photo.png", "png"),
+            ("yarn.lock", "lock"),
+            ("Cargo.lock", "lock"),
+            ("dist/bundle.js", "bundle"),
+            ("node_modules/pkg/index.js", "dep"),
+        ] {
+            let full_path = app_dir.join(path);
+            fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            fs::write(full_path, content).unwrap();
+        }
+
+        let rules = ScanRules::for_app_dir(app_dir);
+        let mut files: Vec<String> = scan_source_files(app_dir, &rules)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.replace('\\', "/"))
+            .collect();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                ".gitignore",
+                ".moldableignore",
+                "dist/bundle.js",
+                "moldable.json",
+                "public/icon.svg",
+                "public/photo.png",
+                "src/page.tsx",
+                "yarn.lock",
+            ]
+        );
+    }
+
+    // ==================== BINARY FILE TESTS ====================
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(b"GIF89a\x00\x01"));
+        assert!(!looks_binary(b"plain text"));
+        assert!(!looks_binary(b""));
+    }
+
+    #[test]
+    fn test_scan_captures_binary_files_under_threshold() {
+        let temp_dir = TempDir::new().unwrap();
+        let app_dir = temp_dir.path().join("app");
+        let store_dir = temp_dir.path().join(BLOB_STORE_DIR);
+        write_settled(
+            &app_dir.join("moldable.json"),
+            br#"{"checkpoint": {"maxBinaryBytes": 64}}"#,
+        );
+        write_settled(&app_dir.join("public/icon.png"), &[0x89, b'P', b'N', b'G', 0, 1]);
+        write_settled(&app_dir.join("public/hero.png"), &[0u8; 128]);
+        write_settled(&app_dir.join("data/app.bin.dat"), &[1, 0, 2, 0]);
+        write_settled(&app_dir.join("data/large.dat"), &[0u8; 128]);
+        write_settled(&app_dir.join("src/page.tsx"), &[b'x'; 128]);
+
+        let scan =
+            scan_working_tree(&app_dir, &StatCache::new(&app_dir), Some(&store_dir)).unwrap();
+        let mut captured: Vec<(String, bool)> = scan
+            .files
+            .iter()
+            .map(|f| (f.path.replace('\\', "/"), f.binary))
+            .collect();
+        captured.sort();
+
+        assert_eq!(
+            captured,
+            vec![
+                ("data/app.bin.dat".to_string(), true),
+                ("moldable.json".to_string(), false),
+                ("public/icon.png".to_string(), true),
+                ("src/page.tsx".to_string(), false),
+            ]
+        );
+
+        // Binary content round-trips through the blob store unchanged
+        let icon = scan.files.iter().find(|f| f.path.ends_with("icon.png")).unwrap();
+        assert_eq!(
+            read_blob_at_path(&store_dir, icon.hash.as_ref().unwrap()).unwrap(),
+            vec![0x89, b'P', b'N', b'G', 0, 1]
+        );
+
+        // The binary flag survives a stat cache hit
+        let warm = scan_working_tree(&app_dir, &scan.cache, Some(&store_dir)).unwrap();
+        assert_eq!(warm.files_hashed, 0);
+        assert!(warm.files.iter().find(|f| f.path.ends_with("icon.png")).unwrap().binary);
+    }
 }
@@ -42,6 +42,12 @@ pub mod conversations;
 // Checkpoints (file snapshot/restore for undo)
 pub mod checkpoints;
 
+// Git-backed checkpoint storage
+pub mod checkpoint_git;
+
+// Checkpoint export/import archives
+pub mod checkpoint_archive;
+
 // Preferences
 pub mod preferences;
 use preferences::{load_shared_config, save_shared_config, migrate_security_preferences};
@@ -75,6 +81,15 @@ use gateway::GatewayState;
 // HTTP API server for AI tools
 pub mod api_server;
 
+// Bearer-token authentication for the API server
+pub mod api_auth;
+
+// Error model for the API server
+pub mod api_error;
+
+// OpenAPI description of the API server
+pub mod api_openapi;
+
 // App registration and detection
 pub mod apps;
 use apps::get_registered_apps;
@@ -349,9 +364,16 @@ pub fn run() {
             // Checkpoint commands (from checkpoints module)
             checkpoints::create_checkpoint,
             checkpoints::list_checkpoints,
+            checkpoints::diff_checkpoints,
             checkpoints::restore_checkpoint,
+            checkpoints::restore_checkpoint_paths,
+            checkpoints::redo_restore,
             checkpoints::cleanup_checkpoints,
+            checkpoints::collect_checkpoint_garbage,
+            checkpoints::verify_checkpoints,
             checkpoints::delete_app_checkpoints,
+            checkpoint_archive::export_checkpoints,
+            checkpoint_archive::import_checkpoints,
             // Audio capture commands (from audio module)
             audio::is_system_audio_available,
             audio::start_audio_capture,
@@ -556,6 +578,9 @@ pub fn run() {
             // Start watching config file for changes
             start_config_watcher(app.handle().clone());
 
+            // Enforce the checkpoint retention policy in the background
+            checkpoints::start_checkpoint_gc();
+
             // Clean up any orphaned processes from previous runs
             let app_state_for_cleanup = app.state::<AppState>();
             cleanup_all_orphaned_apps(get_registered_apps, app_state_for_cleanup.inner());
@@ -9,7 +9,7 @@
 //! negative PGID (e.g., `kill -TERM -<pgid>`), which delivers the signal to all processes
 //! in that group.
 
-use crate::apps::{get_registered_apps, update_registered_app_port};
+use crate::apps::{find_available_port, get_registered_apps, update_registered_app_port};
 use crate::codemods::run_pending_codemods;
 use crate::env::get_merged_env_vars;
 use crate::install_state::{
@@ -17,7 +17,7 @@ use crate::install_state::{
 };
 use crate::paths::get_workspaces_config_internal;
 use crate::paths::{get_home_dir, get_moldable_root};
-use crate::ports::kill_process_tree;
+use crate::ports::{is_port_available, kill_process_tree};
 use crate::runtime;
 use crate::types::{AppInstance, AppStatus, RegisteredApp};
 use log::{info, warn};
@@ -1347,13 +1347,47 @@ pub fn start_app(
 
 #[tauri::command]
 pub fn stop_app(app_id: String, state: State<AppState>) -> Result<AppStatus, String> {
+    stop_app_internal(&app_id, state.inner())
+}
+
+#[tauri::command]
+pub fn get_app_status(app_id: String, state: State<AppState>) -> Result<AppStatus, String> {
+    get_app_status_internal(app_id, state.inner())
+}
+
+#[tauri::command]
+pub fn get_app_logs(app_id: String, state: State<AppState>) -> Result<Vec<String>, String> {
+    get_app_logs_internal(&app_id, state.inner())
+}
+
+/// Start a registered app of the active workspace using its configured command.
+///
+/// Mirrors the frontend's auto-start: the app's preferred port is used when it's
+/// free (or the app requires it), otherwise the next available one.
+pub fn start_registered_app(app_id: &str, state: &AppState) -> Result<AppStatus, String> {
+    let app = get_registered_apps()?
+        .into_iter()
+        .find(|app| app.id == app_id)
+        .ok_or_else(|| format!("App '{}' is not registered in this workspace", app_id))?;
+
+    let port = if app.requires_port || is_port_available(app.port) {
+        app.port
+    } else {
+        find_available_port(app.port)
+    };
+
+    start_app_internal(app.id, app.path, app.command, app.args, Some(port), state)
+}
+
+/// Stop an app process (internal implementation)
+pub fn stop_app_internal(app_id: &str, state: &AppState) -> Result<AppStatus, String> {
     let mut app_state = state.0.lock().map_err(|e| e.to_string())?;
 
-    if let Some(mut app_proc) = app_state.processes.remove(&app_id) {
+    if let Some(mut app_proc) = app_state.processes.remove(app_id) {
         // Save output before killing
         app_state
             .last_errors
-            .insert(app_id.clone(), app_proc.output_lines.clone());
+            .insert(app_id.to_string(), app_proc.output_lines.clone());
 
         let pid = app_proc.child.id();
 
@@ -1373,8 +1407,8 @@ pub fn stop_app(app_id: String, state: State<AppState>) -> Result<AppStatus, Str
     })
 }
 
-#[tauri::command]
-pub fn get_app_status(app_id: String, state: State<AppState>) -> Result<AppStatus, String> {
+/// Get an app's status, retrying once after a Next dev lock error (internal implementation)
+pub fn get_app_status_internal(app_id: String, state: &AppState) -> Result<AppStatus, String> {
     let mut retry_plan: Option<(RegisteredApp, Option<u16>)> = None;
     let mut immediate_status: Option<AppStatus> = None;
     let mut app_state = state.0.lock().map_err(|e| e.to_string())?;
@@ -1469,7 +1503,7 @@ pub fn get_app_status(app_id: String, state: State<AppState>) -> Result<AppStatu
             app.command,
             app.args,
             port,
-            state.inner(),
+            state,
             true, // force_cleanup
         ) {
             Ok(status) => return Ok(status),
@@ -1503,8 +1537,8 @@ pub fn get_app_status(app_id: String, state: State<AppState>) -> Result<AppStatu
     })
 }
 
-#[tauri::command]
-pub fn get_app_logs(app_id: String, state: State<AppState>) -> Result<Vec<String>, String> {
+/// Get install progress plus captured output for an app (internal implementation)
+pub fn get_app_logs_internal(app_id: &str, state: &AppState) -> Result<Vec<String>, String> {
     let app_state = state.0.lock().map_err(|e| e.to_string())?;
     let install_lines = get_registered_apps()
         .ok()
@@ -1513,7 +1547,7 @@ pub fn get_app_logs(app_id: String, state: State<AppState>) -> Result<Vec<String
         .unwrap_or_default();
 
     // First check running process
-    if let Some(app_proc) = app_state.processes.get(&app_id) {
+    if let Some(app_proc) = app_state.processes.get(app_id) {
         let mut lines = install_lines;
         lines.extend(app_proc.output_lines.clone());
         return Ok(lines);
@@ -1524,7 +1558,7 @@ pub fn get_app_logs(app_id: String, state: State<AppState>) -> Result<Vec<String
     lines.extend(
         app_state
             .last_errors
-            .get(&app_id)
+            .get(app_id)
             .cloned()
             .unwrap_or_default(),
     );
@@ -241,7 +241,7 @@ fn swap_app_directory(staging_dir: &Path, app_dir: &Path) -> Result<(), String>
     Ok(())
 }
 
-fn safe_zip_entry_name(file: &zip::read::ZipFile) -> Result<PathBuf, String> {
+pub(crate) fn safe_zip_entry_name(file: &zip::read::ZipFile) -> Result<PathBuf, String> {
     let raw_path = Path::new(file.name());
     if raw_path.components().any(|component| {
         matches!(
@@ -185,6 +185,38 @@ pub struct MoldableManifest {
     pub args: Option<Vec<String>>,
     #[serde(default)]
     pub env: Vec<EnvRequirement>,
+    /// Which files checkpoints capture, on top of .gitignore and .moldableignore
+    #[serde(default)]
+    pub checkpoint: Option<CheckpointConfig>,
+}
+
+/// Checkpoint scanning rules from the `checkpoint` section of moldable.json
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct CheckpointConfig {
+    /// Gitignore-style patterns to capture even if ignored by default or by .gitignore
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Gitignore-style patterns to leave out of checkpoints
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Binary files larger than this many bytes are not captured (default 1MB)
+    #[serde(default, rename = "maxBinaryBytes")]
+    pub max_binary_bytes: Option<u64>,
+    /// Where checkpoint content is kept
+    #[serde(default)]
+    pub storage: CheckpointStorage,
+}
+
+/// Checkpoint storage backend for an app
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckpointStorage {
+    /// Moldable's own content-addressed blob store
+    #[default]
+    Blobs,
+    /// Commits on a hidden ref in the app's git repository (blob store if
+    /// the app isn't in one)
+    Git,
 }
 
 /// Environment status for an app
@@ -17,6 +17,8 @@ export interface CheckpointSummary {
   totalBytes: number
   /** Whether this checkpoint differs from the previous one (files were modified) */
   hasChanges: boolean
+  /** Tells apart several checkpoints of the same message, e.g. one per tool call */
+  label?: string
 }
 
 /**
@@ -38,6 +40,9 @@ export interface RestoreResult {
   filesRestored: number
   filesDeleted: number
   bytesWritten: number
+  /** Safety snapshot taken before restoring; pass to `redo_restore` to undo */
+  preRestoreMessageId?: string
+  preRestoreSnapshotId?: string
 }
 
 /**
@@ -153,11 +158,15 @@ export function useCheckpoints(
   /**
    * Restore files to a checkpoint state.
    *
-   * @param messageId - The message ID of the checkpoint to restore
+   * @param messageId - The message ID of the checkpoint to restore (its latest checkpoint)
+   * @param snapshotId - Optional ID of a specific checkpoint, takes precedence over messageId
    * @returns The restore result
    */
   const restore = useCallback(
-    async (messageId: string): Promise<RestoreResult | null> => {
+    async (
+      messageId: string,
+      snapshotId?: string,
+    ): Promise<RestoreResult | null> => {
       if (!enabled || !appId || !conversationId) {
         return null
       }
@@ -167,6 +176,7 @@ export function useCheckpoints(
           appId,
           conversationId,
           messageId,
+          snapshotId,
         })
         return result
       } catch (err) {
@@ -219,11 +229,11 @@ export function useCheckpoints(
   )
 
   /**
-   * Get checkpoint info for a specific message.
+   * Get checkpoint info for a specific message (its latest checkpoint).
    */
   const getCheckpoint = useCallback(
     (messageId: string): CheckpointSummary | undefined => {
-      return checkpoints.find((cp) => cp.messageId === messageId)
+      return [...checkpoints].reverse().find((cp) => cp.messageId === messageId)
     },
     [checkpoints],
   )
@@ -208,6 +208,9 @@ const SUBAGENT_BLOCKED_TOOLS = new Set([
   'unregisterApp',
   'deleteAppData',
   'deleteApp',
+  'startApp',
+  'stopApp',
+  'restartApp',
   'gatewaySpawnSubagent',
   'gatewayCreateCronJob',
   'gatewayRunCronJob',
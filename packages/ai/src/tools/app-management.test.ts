@@ -16,6 +16,7 @@ describe('createAppManagementTools', () => {
 
   afterEach(() => {
     vi.restoreAllMocks()
+    vi.unstubAllEnvs()
   })
 
   describe('getAppInfo', () => {
@@ -347,6 +348,128 @@ describe('createAppManagementTools', () => {
     })
   })
 
+  describe('app process tools', () => {
+    it('starts an app with a POST to its start endpoint', async () => {
+      const tools = createAppManagementTools({ apiServerPort: 39102 })
+
+      const fetchSpy = vi.spyOn(global, 'fetch').mockResolvedValueOnce({
+        ok: true,
+        json: async () => ({
+          success: true,
+          appId: 'scribo',
+          running: true,
+          pid: 4242,
+          port: 3001,
+          recentOutput: ['ready on localhost:3001'],
+        }),
+      } as Response)
+
+      const result = (await tools.startApp.execute!(
+        { appId: 'scribo' },
+        ctx,
+      )) as { success: boolean; running?: boolean; port?: number }
+
+      expect(fetchSpy).toHaveBeenCalledWith(
+        'http://127.0.0.1:39102/api/apps/scribo/start',
+        expect.objectContaining({ method: 'POST' }),
+      )
+      expect(result.success).toBe(true)
+      expect(result.running).toBe(true)
+      expect(result.port).toBe(3001)
+    })
+
+    it('reads status and logs with GET requests', async () => {
+      const tools = createAppManagementTools({ apiServerPort: 39102 })
+
+      const fetchSpy = vi
+        .spyOn(global, 'fetch')
+        .mockResolvedValueOnce({
+          ok: true,
+          json: async () => ({ success: true, running: false, exitCode: 1 }),
+        } as Response)
+        .mockResolvedValueOnce({
+          ok: true,
+          json: async () => ({ success: true, lines: ['Error: boom'] }),
+        } as Response)
+
+      const status = (await tools.getAppStatus.execute!(
+        { appId: 'scribo' },
+        ctx,
+      )) as { running?: boolean; exitCode?: number }
+      const logs = (await tools.getAppLogs.execute!(
+        { appId: 'scribo' },
+        ctx,
+      )) as { lines?: string[] }
+
+      expect(fetchSpy).toHaveBeenNthCalledWith(
+        1,
+        'http://127.0.0.1:39102/api/apps/scribo/status',
+        expect.objectContaining({ method: 'GET' }),
+      )
+      expect(fetchSpy).toHaveBeenNthCalledWith(
+        2,
+        'http://127.0.0.1:39102/api/apps/scribo/logs',
+        expect.objectContaining({ method: 'GET' }),
+      )
+      expect(status.running).toBe(false)
+      expect(status.exitCode).toBe(1)
+      expect(logs.lines).toEqual(['Error: boom'])
+    })
+
+    it('returns the server error for unregistered apps', async () => {
+      const tools = createAppManagementTools({ apiServerPort: 39102 })
+
+      vi.spyOn(global, 'fetch').mockResolvedValueOnce({
+        ok: false,
+        json: async () => ({
+          success: false,
+          error: "App 'ghost' is not registered in this workspace",
+        }),
+      } as Response)
+
+      const result = (await tools.restartApp.execute!(
+        { appId: 'ghost' },
+        ctx,
+      )) as { success: boolean; error?: string }
+
+      expect(result.success).toBe(false)
+      expect(result.error).toContain('not registered')
+    })
+  })
+
+  describe('API server authentication', () => {
+    it('sends the API token as a bearer token', async () => {
+      const tools = createAppManagementTools({ apiToken: 'secret-token' })
+
+      const fetchSpy = vi.spyOn(global, 'fetch').mockResolvedValueOnce({
+        ok: true,
+        json: async () => ({ success: true, appId: 'test' }),
+      } as Response)
+
+      await tools.getAppInfo.execute!({ appId: 'test' }, ctx)
+
+      const init = fetchSpy.mock.calls[0]?.[1] as RequestInit
+      expect(init.headers).toMatchObject({
+        Authorization: 'Bearer secret-token',
+      })
+    })
+
+    it('reads the token from MOLDABLE_API_TOKEN by default', async () => {
+      vi.stubEnv('MOLDABLE_API_TOKEN', 'env-token')
+      const tools = createAppManagementTools()
+
+      const fetchSpy = vi.spyOn(global, 'fetch').mockResolvedValueOnce({
+        ok: true,
+        json: async () => ({ success: true, appId: 'test' }),
+      } as Response)
+
+      await tools.getAppInfo.execute!({ appId: 'test' }, ctx)
+
+      const init = fetchSpy.mock.calls[0]?.[1] as RequestInit
+      expect(init.headers).toMatchObject({ Authorization: 'Bearer env-token' })
+    })
+  })
+
   describe('input validation', () => {
     it('validates appId format in schema', () => {
       const tools = createAppManagementTools()
@@ -12,6 +12,8 @@ const DEFAULT_API_SERVER_PORT = 39102
 export interface AppManagementToolsOptions {
   /** API server port (passed from frontend which knows the actual port) */
   apiServerPort?: number
+  /** API server bearer token (defaults to the MOLDABLE_API_TOKEN env var) */
+  apiToken?: string
 }
 
 /**
@@ -62,13 +64,67 @@ interface DeleteAppResponse {
   error?: string
 }
 
+/**
+ * Response from the app process endpoints (start, stop, restart, status)
+ */
+interface AppProcessResponse {
+  success: boolean
+  appId?: string
+  running?: boolean
+  pid?: number
+  exitCode?: number
+  port?: number
+  recentOutput?: string[]
+  error?: string
+}
+
+/**
+ * Response from the app logs endpoint
+ */
+interface AppLogsResponse {
+  success: boolean
+  appId?: string
+  lines?: string[]
+  error?: string
+}
+
+type AppProcessAction = 'start' | 'stop' | 'restart' | 'status'
+
+/**
+ * Turn a fetch failure into a tool error result
+ */
+function connectionError(error: unknown, fallback: string) {
+  if (
+    error instanceof Error &&
+    (error.message.includes('ECONNREFUSED') ||
+      error.message.includes('fetch failed'))
+  ) {
+    return {
+      success: false,
+      error:
+        'Could not connect to Moldable API server. Make sure Moldable desktop is running.',
+    }
+  }
+  return {
+    success: false,
+    error: error instanceof Error ? error.message : fallback,
+  }
+}
+
 /**
  * Create app management tools for the Moldable AI agent
  */
 export function createAppManagementTools(
   options: AppManagementToolsOptions = {},
 ) {
-  const { apiServerPort = DEFAULT_API_SERVER_PORT } = options
+  const {
+    apiServerPort = DEFAULT_API_SERVER_PORT,
+    apiToken = process.env.MOLDABLE_API_TOKEN,
+  } = options
+  const headers: Record<string, string> = {
+    'Content-Type': 'application/json',
+    ...(apiToken ? { Authorization: `Bearer ${apiToken}` } : {}),
+  }
 
   const appIdSchema = z.object({
     appId: z
@@ -77,6 +133,42 @@ export function createAppManagementTools(
       .describe('The ID of the app (e.g., "my-app")'),
   })
 
+  const appProcessUrl = (appId: string, action: AppProcessAction | 'logs') =>
+    `http://127.0.0.1:${apiServerPort}/api/apps/${encodeURIComponent(appId)}/${action}`
+
+  const runAppProcessAction = async (
+    appId: string,
+    action: AppProcessAction,
+  ) => {
+    try {
+      const response = await fetch(appProcessUrl(appId, action), {
+        method: action === 'status' ? 'GET' : 'POST',
+        headers,
+      })
+
+      const result: AppProcessResponse = await response.json()
+
+      if (!result.success) {
+        return {
+          success: false,
+          error: result.error || `Failed to ${action} app`,
+        }
+      }
+
+      return {
+        success: true,
+        appId: result.appId,
+        running: result.running,
+        pid: result.pid,
+        exitCode: result.exitCode,
+        port: result.port,
+        recentOutput: result.recentOutput,
+      }
+    } catch (error) {
+      return connectionError(error, `Failed to ${action} app`)
+    }
+  }
+
   return {
     /**
      * Get information about an app, including which workspaces it's installed in.
@@ -91,7 +183,7 @@ export function createAppManagementTools(
             `http://127.0.0.1:${apiServerPort}/api/app-info`,
             {
               method: 'POST',
-              headers: { 'Content-Type': 'application/json' },
+              headers,
               body: JSON.stringify({ appId }),
             },
           )
@@ -150,7 +242,7 @@ export function createAppManagementTools(
             `http://127.0.0.1:${apiServerPort}/api/unregister-app`,
             {
               method: 'POST',
-              headers: { 'Content-Type': 'application/json' },
+              headers,
               body: JSON.stringify({ appId }),
             },
           )
@@ -209,7 +301,7 @@ export function createAppManagementTools(
             `http://127.0.0.1:${apiServerPort}/api/delete-app-data`,
             {
               method: 'POST',
-              headers: { 'Content-Type': 'application/json' },
+              headers,
               body: JSON.stringify({ appId }),
             },
           )
@@ -252,6 +344,75 @@ export function createAppManagementTools(
       },
     }),
 
+    /**
+     * Start an app so its output can be checked after editing it.
+     */
+    startApp: tool({
+      description: `Start a Moldable app in the current workspace using its configured command. Does nothing if the app is already running. Use this after editing an app, then call getAppStatus or getAppLogs to check that it runs without errors.`,
+      inputSchema: zodSchema(appIdSchema),
+      execute: async ({ appId }) => runAppProcessAction(appId, 'start'),
+    }),
+
+    /**
+     * Stop a running app and its whole process tree.
+     */
+    stopApp: tool({
+      description: `Stop a running Moldable app. Its last output stays available through getAppLogs.`,
+      inputSchema: zodSchema(appIdSchema),
+      execute: async ({ appId }) => runAppProcessAction(appId, 'stop'),
+    }),
+
+    /**
+     * Restart an app, e.g. after changing its dependencies or config.
+     */
+    restartApp: tool({
+      description: `Restart a Moldable app (stop it if running, then start it again). Use this when a change isn't picked up by hot reload, e.g. after installing dependencies or editing config files.`,
+      inputSchema: zodSchema(appIdSchema),
+      execute: async ({ appId }) => runAppProcessAction(appId, 'restart'),
+    }),
+
+    /**
+     * Check whether an app is running, with its recent output.
+     */
+    getAppStatus: tool({
+      description: `Check whether a Moldable app is running. Returns its pid, port, exit code (if it stopped) and recent output.`,
+      inputSchema: zodSchema(appIdSchema),
+      execute: async ({ appId }) => runAppProcessAction(appId, 'status'),
+    }),
+
+    /**
+     * Read an app's captured output.
+     */
+    getAppLogs: tool({
+      description: `Read a Moldable app's install progress and captured stdout/stderr. If the app has stopped, returns the output of its last run. Use this to find build or runtime errors after editing an app.`,
+      inputSchema: zodSchema(appIdSchema),
+      execute: async ({ appId }) => {
+        try {
+          const response = await fetch(appProcessUrl(appId, 'logs'), {
+            method: 'GET',
+            headers,
+          })
+
+          const result: AppLogsResponse = await response.json()
+
+          if (!result.success) {
+            return {
+              success: false,
+              error: result.error || 'Failed to get app logs',
+            }
+          }
+
+          return {
+            success: true,
+            appId: result.appId,
+            lines: result.lines,
+          }
+        } catch (error) {
+          return connectionError(error, 'Failed to get app logs')
+        }
+      },
+    }),
+
     /**
      * DANGEROUS: Permanently delete an app from Moldable.
      * This removes the app from ALL workspaces and deletes all code and data.
@@ -268,7 +429,7 @@ export function createAppManagementTools(
             `http://127.0.0.1:${apiServerPort}/api/delete-app`,
             {
               method: 'POST',
-              headers: { 'Content-Type': 'application/json' },
+              headers,
               body: JSON.stringify({ appId }),
             },
           )
@@ -323,4 +484,9 @@ export const APP_MANAGEMENT_TOOL_DESCRIPTIONS = {
   deleteAppData:
     "Delete an app's data in the current workspace (app stays installed)",
   deleteApp: 'DANGEROUS: Permanently delete an app from all workspaces',
+  startApp: 'Start an app in the current workspace',
+  stopApp: 'Stop a running app',
+  restartApp: 'Restart an app',
+  getAppStatus: 'Check whether an app is running',
+  getAppLogs: "Read an app's captured output",
 } as const
@@ -51,6 +51,8 @@ export type MoldableToolsOptions = {
   onCommandProgress?: CommandProgressCallback
   /** API server port for scaffold tools (passed from frontend) */
   apiServerPort?: number
+  /** API server bearer token (defaults to the MOLDABLE_API_TOKEN env var) */
+  apiToken?: string
   /** Whether to require user approval for unsandboxed commands (default: true) */
   requireUnsandboxedApproval?: boolean
   /** Whether to require user approval for dangerous commands (default: true) */
@@ -73,6 +75,7 @@ export function createMoldableTools(options: MoldableToolsOptions = {}) {
     googleSearchEngineId,
     onCommandProgress,
     apiServerPort,
+    apiToken,
     requireUnsandboxedApproval,
     requireDangerousCommandApproval,
     dangerousPatterns,
@@ -100,8 +103,8 @@ export function createMoldableTools(options: MoldableToolsOptions = {}) {
       searchEngineId: googleSearchEngineId,
     }),
     ...createSkillsTools(),
-    ...createScaffoldTools({ apiServerPort }),
-    ...createAppManagementTools({ apiServerPort }),
+    ...createScaffoldTools({ apiServerPort, apiToken }),
+    ...createAppManagementTools({ apiServerPort, apiToken }),
     ...createToolOutputTools({ outputDir }),
   }
 }
@@ -169,7 +172,17 @@ export const TOOL_CATEGORIES = {
   search: ['grep', 'globFileSearch'],
   web: ['webSearch'],
   scaffold: ['scaffoldApp'],
-  appManagement: ['getAppInfo', 'unregisterApp', 'deleteAppData', 'deleteApp'],
+  appManagement: [
+    'getAppInfo',
+    'unregisterApp',
+    'deleteAppData',
+    'deleteApp',
+    'startApp',
+    'stopApp',
+    'restartApp',
+    'getAppStatus',
+    'getAppLogs',
+  ],
   skills: [
     'listSkillRepos',
     'listAvailableSkills',
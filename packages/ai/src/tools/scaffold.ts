@@ -17,6 +17,8 @@ const DEFAULT_API_SERVER_PORT = 39102
 export interface ScaffoldToolsOptions {
   /** API server port (passed from frontend which knows the actual port) */
   apiServerPort?: number
+  /** API server bearer token (defaults to the MOLDABLE_API_TOKEN env var) */
+  apiToken?: string
 }
 
 /**
@@ -40,7 +42,10 @@ interface CreateAppResponse {
  * Create app scaffolding tools
  */
 export function createScaffoldTools(options: ScaffoldToolsOptions = {}) {
-  const { apiServerPort = DEFAULT_API_SERVER_PORT } = options
+  const {
+    apiServerPort = DEFAULT_API_SERVER_PORT,
+    apiToken = process.env.MOLDABLE_API_TOKEN,
+  } = options
 
   const scaffoldAppSchema = z.object({
     appId: z
@@ -94,6 +99,7 @@ export function createScaffoldTools(options: ScaffoldToolsOptions = {}) {
               method: 'POST',
               headers: {
                 'Content-Type': 'application/json',
+                ...(apiToken ? { Authorization: `Bearer ${apiToken}` } : {}),
               },
               body: JSON.stringify({
                 appId,